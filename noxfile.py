@@ -21,6 +21,8 @@ def build_wheel(session):
     # quick test of the wheel
     session.install(wheel)
     session.run('python', '-c', 'import lazrs')
+    session.install('pytest')
+    session.run('pytest', 'tests')
 
     # Save the wheel as its going to be erased by the next cargo clean
     shutil.copy(wheel, 'dist')
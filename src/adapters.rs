@@ -1,12 +1,16 @@
-use std::io::SeekFrom;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::os::raw::c_char;
+use std::path::PathBuf;
 
 use pyo3::ffi::Py_ssize_t;
 use pyo3::types::{PyAnyMethods, PyBytesMethods};
 use pyo3::{IntoPyObject, PyAny, PyResult, Python};
 
+use crate::selective::SelectiveReader;
+
 fn to_other_io_error(message: String) -> std::io::Error {
-    std::io::Error::new(std::io::ErrorKind::Other, message)
+    std::io::Error::other(message)
 }
 
 fn py_seek_args_from_rust_seek(
@@ -54,6 +58,25 @@ fn py_seek_args_from_rust_seek(
     }
 }
 
+/// Like `Python::attach`, but saves & restores the pending Python exception (if any).
+///
+/// The file object may be called while an exception is being raised
+/// (e.g. a `BufWriter` flushing when the object owning it is dropped),
+/// and calling into Python with an exception set is not allowed.
+fn attach_preserving_error<T, F>(f: F) -> T
+where
+    F: for<'py> FnOnce(Python<'py>) -> T,
+{
+    Python::attach(|py| {
+        let pending = pyo3::PyErr::take(py);
+        let result = f(py);
+        if let Some(err) = pending {
+            err.restore(py);
+        }
+        result
+    })
+}
+
 #[derive(Clone)]
 pub(crate) struct PyFileObject {
     file_obj: pyo3::Py<PyAny>,
@@ -77,9 +100,12 @@ impl PyFileObject {
     }
 }
 
+// When a method of the Python file object raises, the `PyErr` is
+// wrapped in the `io::Error` so that it can be re-raised once the
+// error gets back to Python (see `errors::IntoPyErr`).
 impl std::io::Read for PyFileObject {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        Python::attach(|py| {
+        attach_preserving_error(|py| {
             if let Some(ref readinto) = self.readinto_fn {
                 let memview = unsafe {
                     let view_object = pyo3::ffi::PyMemoryView_FromMemory(
@@ -93,9 +119,7 @@ impl std::io::Read for PyFileObject {
                 readinto
                     .call1(py, (memview,))
                     .and_then(|num_bytes_read| num_bytes_read.extract::<usize>(py))
-                    .map_err(|_err| {
-                        to_other_io_error("Failed to use readinto to read bytes".to_string())
-                    })
+                    .map_err(std::io::Error::other)
             } else {
                 let num_bytes_to_read: pyo3::Py<PyAny> =
                     buf.len().into_pyobject(py).unwrap().into_any().unbind();
@@ -105,12 +129,7 @@ impl std::io::Read for PyFileObject {
                     .as_ref()
                     .ok_or_else(|| to_other_io_error("No read method on file object".to_string()))?
                     .call1(py, (num_bytes_to_read,))
-                    .map_err(|_err| {
-                        std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            "Failed to call read".to_string(),
-                        )
-                    })?;
+                    .map_err(std::io::Error::other)?;
 
                 match object.cast_bound::<pyo3::types::PyBytes>(py) {
                     Ok(py_bytes) => {
@@ -119,8 +138,7 @@ impl std::io::Read for PyFileObject {
                         buf[..shortest].copy_from_slice(read_bytes);
                         Ok(read_bytes.len())
                     }
-                    Err(_) => Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
+                    Err(_) => Err(std::io::Error::other(
                         "read did not return bytes".to_string(),
                     )),
                 }
@@ -131,7 +149,7 @@ impl std::io::Read for PyFileObject {
 
 impl std::io::Write for PyFileObject {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        Python::attach(|py| {
+        attach_preserving_error(|py| {
             let memview = unsafe {
                 let view_object = pyo3::ffi::PyMemoryView_FromMemory(
                     buf.as_ptr() as *mut c_char,
@@ -144,18 +162,18 @@ impl std::io::Write for PyFileObject {
 
             self.write_fn
                 .as_ref()
-                .ok_or_else(|| to_other_io_error("Ne read method on file object".to_string()))?
+                .ok_or_else(|| to_other_io_error("No write method on file object".to_string()))?
                 .call1(py, (memview,))
                 .and_then(|ret_val| ret_val.extract::<usize>(py))
-                .map_err(|_err| to_other_io_error("Failed to call write".to_string()))
+                .map_err(std::io::Error::other)
         })
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        Python::attach(|py| {
+        attach_preserving_error(|py| {
             self.file_obj
                 .call_method0(py, "flush")
-                .map_err(|_err| to_other_io_error("Failed to call flush".to_string()))?;
+                .map_err(std::io::Error::other)?;
             Ok(())
         })
     }
@@ -163,39 +181,78 @@ impl std::io::Write for PyFileObject {
 
 impl std::io::Seek for PyFileObject {
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
-        Python::attach(|py| {
+        attach_preserving_error(|py| {
             let args = py_seek_args_from_rust_seek(pos, py);
             let new_pos = self
                 .file_obj
                 .call_method(py, "seek", args, None)
                 .and_then(|py_long| py_long.extract::<u64>(py))
-                .map_err(|_err| to_other_io_error("Failed to call seek".to_string()))?;
+                .map_err(std::io::Error::other)?;
             Ok(new_pos)
         })
     }
 }
 
-pub struct BufReadWritePyFileObject {
-    input: std::io::BufReader<PyFileObject>,
-    output: std::io::BufWriter<PyFileObject>,
+/// Destinations that can be truncated
+pub(crate) trait Truncate {
+    /// Flushes, then truncates the destination to `len` bytes
+    fn truncate(&mut self, len: u64) -> std::io::Result<()>;
+}
+
+impl Truncate for PyFileObject {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        attach_preserving_error(|py| {
+            self.file_obj
+                .call_method1(py, "truncate", (len,))
+                .map_err(std::io::Error::other)?;
+            Ok(())
+        })
+    }
+}
+
+impl Truncate for File {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        self.set_len(len)
+    }
+}
+
+impl<T: Write + Truncate> Truncate for BufWriter<T> {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        self.flush()?;
+        self.get_mut().truncate(len)
+    }
+}
+
+pub struct BufReadWrite<T: Read + Write + Seek> {
+    input: BufReader<T>,
+    output: BufWriter<T>,
 }
 
+pub type BufReadWritePyFileObject = BufReadWrite<PyFileObject>;
+
 impl BufReadWritePyFileObject {
     pub(crate) fn new(file: PyFileObject) -> Self {
-        let input = std::io::BufReader::new(file.clone());
-        let output = std::io::BufWriter::new(file);
+        Self::from_parts(file.clone(), file)
+    }
+}
+
+impl<T: Read + Write + Seek> BufReadWrite<T> {
+    /// `input` and `output` must be two handles to the same underlying file.
+    pub(crate) fn from_parts(input: T, output: T) -> Self {
+        let input = BufReader::new(input);
+        let output = BufWriter::new(output);
 
         Self { input, output }
     }
 }
 
-impl std::io::Read for BufReadWritePyFileObject {
+impl<T: Read + Write + Seek> Read for BufReadWrite<T> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         self.input.read(buf)
     }
 }
 
-impl std::io::Write for BufReadWritePyFileObject {
+impl<T: Read + Write + Seek> Write for BufReadWrite<T> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         self.output.write(buf)
     }
@@ -205,7 +262,13 @@ impl std::io::Write for BufReadWritePyFileObject {
     }
 }
 
-impl std::io::Seek for BufReadWritePyFileObject {
+impl<T: Read + Write + Seek + Truncate> Truncate for BufReadWrite<T> {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        self.output.truncate(len)
+    }
+}
+
+impl<T: Read + Write + Seek> Seek for BufReadWrite<T> {
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
         // We have to get the absolute pos after the first seek
         // and use SeekFrom::Start for the second seek because if the orginal
@@ -214,3 +277,260 @@ impl std::io::Seek for BufReadWritePyFileObject {
         self.input.seek(SeekFrom::Start(pos))
     }
 }
+
+/// Returns the path if the object is a `str` or an `os.PathLike`
+fn extract_path(py: Python, object: &pyo3::Py<PyAny>) -> Option<PathBuf> {
+    object.extract::<PathBuf>(py).ok()
+}
+
+/// Positions the file at the start of the point data.
+///
+/// If the file starts with a LAS header, the position will be the
+/// header's `offset_to_point_data`, otherwise the file is considered to
+/// only contain point data and the position is the start of the file.
+fn seek_to_point_data(file: &mut File) -> std::io::Result<()> {
+    const OFFSET_TO_POINT_DATA_POS: u64 = 96;
+
+    let mut signature = [0u8; 4];
+    if file.read_exact(&mut signature).is_ok() && &signature == b"LASF" {
+        let mut offset = [0u8; 4];
+        file.seek(SeekFrom::Start(OFFSET_TO_POINT_DATA_POS))?;
+        file.read_exact(&mut offset)?;
+        file.seek(SeekFrom::Start(u64::from(u32::from_le_bytes(offset))))?;
+    } else {
+        file.seek(SeekFrom::Start(0))?;
+    }
+    Ok(())
+}
+
+/// Source of data for the decompressors.
+///
+/// When the user gives a path we open it natively, which means
+/// reading never has to go through Python (and the GIL).
+/// See [seek_to_point_data] for where the reading starts.
+pub(crate) enum Reader {
+    PyFile(BufReader<PyFileObject>),
+    File(BufReader<File>),
+    /// Only reads the selected layers, see [SelectiveReader]
+    Selective(Box<SelectiveReader>),
+}
+
+impl Reader {
+    pub(crate) fn new(py: Python, source: pyo3::Py<PyAny>) -> PyResult<Self> {
+        match extract_path(py, &source) {
+            Some(path) => {
+                let mut file = File::open(path)?;
+                seek_to_point_data(&mut file)?;
+                Ok(Self::File(BufReader::new(file)))
+            }
+            None => Ok(Self::PyFile(BufReader::new(PyFileObject::new(py, source)?))),
+        }
+    }
+
+    /// Reads into `buf` from `position`, until `buf` is full or the source ends.
+    ///
+    /// Unlike `read`, the buffer is bypassed, so no bytes past `buf` are read.
+    pub(crate) fn read_unbuffered_at(
+        &mut self,
+        position: u64,
+        buf: &mut [u8],
+    ) -> std::io::Result<usize> {
+        fn read_full<R: Read>(mut source: R, buf: &mut [u8]) -> std::io::Result<usize> {
+            let mut num_read = 0;
+            while num_read < buf.len() {
+                match source.read(&mut buf[num_read..]) {
+                    Ok(0) => break,
+                    Ok(n) => num_read += n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(num_read)
+        }
+
+        // Seeking a BufReader discards its buffer
+        match self {
+            Self::PyFile(f) => {
+                f.seek(SeekFrom::Start(position))?;
+                read_full(f.get_mut(), buf)
+            }
+            Self::File(f) => {
+                f.seek(SeekFrom::Start(position))?;
+                read_full(f.get_mut(), buf)
+            }
+            Self::Selective(f) => {
+                f.seek(SeekFrom::Start(position))?;
+                read_full(f, buf)
+            }
+        }
+    }
+
+    /// Reads exactly `buf.len()` bytes as they are in the source,
+    /// a [SelectiveReader] also reads the layers that are not selected.
+    pub(crate) fn read_exact_raw(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        match self {
+            Self::Selective(f) => f.read_exact_raw(buf),
+            _ => self.read_exact(buf),
+        }
+    }
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::PyFile(f) => f.read(buf),
+            Self::File(f) => f.read(buf),
+            Self::Selective(f) => f.read(buf),
+        }
+    }
+}
+
+impl Seek for Reader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Self::PyFile(f) => f.seek(pos),
+            Self::File(f) => f.seek(pos),
+            Self::Selective(f) => f.seek(pos),
+        }
+    }
+}
+
+/// Destination of data for the compressors.
+///
+/// Paths are written to natively, the file is created if it does not exist,
+/// and the points are written after its existing content
+/// (e.g. the LAS header and VLRs written by the caller beforehand).
+pub(crate) enum Writer {
+    PyFile(BufWriter<PyFileObject>),
+    File(BufWriter<File>),
+}
+
+impl Writer {
+    /// Unlike [Self::new], paths are truncated
+    pub(crate) fn create(py: Python, dest: pyo3::Py<PyAny>) -> PyResult<Self> {
+        match extract_path(py, &dest) {
+            Some(path) => Ok(Self::File(BufWriter::new(File::create(path)?))),
+            None => Ok(Self::PyFile(BufWriter::new(PyFileObject::new(py, dest)?))),
+        }
+    }
+
+    pub(crate) fn new(py: Python, dest: pyo3::Py<PyAny>) -> PyResult<Self> {
+        match extract_path(py, &dest) {
+            Some(path) => {
+                let mut file = std::fs::OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(path)?;
+                file.seek(SeekFrom::End(0))?;
+                Ok(Self::File(BufWriter::new(file)))
+            }
+            None => Ok(Self::PyFile(BufWriter::new(PyFileObject::new(py, dest)?))),
+        }
+    }
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::PyFile(f) => f.write(buf),
+            Self::File(f) => f.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::PyFile(f) => f.flush(),
+            Self::File(f) => f.flush(),
+        }
+    }
+}
+
+impl Seek for Writer {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Self::PyFile(f) => f.seek(pos),
+            Self::File(f) => f.seek(pos),
+        }
+    }
+}
+
+impl Truncate for Writer {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        match self {
+            Self::PyFile(f) => f.truncate(len),
+            Self::File(f) => f.truncate(len),
+        }
+    }
+}
+
+/// Source and destination of data for the appenders.
+///
+/// Paths are opened natively in read + write mode,
+/// see [seek_to_point_data] for where the points are expected to start.
+pub(crate) enum ReadWriter {
+    PyFile(BufReadWritePyFileObject),
+    File(BufReadWrite<File>),
+}
+
+impl ReadWriter {
+    pub(crate) fn new(py: Python, dest: pyo3::Py<PyAny>) -> PyResult<Self> {
+        match extract_path(py, &dest) {
+            Some(path) => {
+                let mut file = std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(path)?;
+                seek_to_point_data(&mut file)?;
+                let clone = file.try_clone()?;
+                Ok(Self::File(BufReadWrite::from_parts(file, clone)))
+            }
+            None => Ok(Self::PyFile(BufReadWritePyFileObject::new(
+                PyFileObject::new(py, dest)?,
+            ))),
+        }
+    }
+}
+
+impl Read for ReadWriter {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::PyFile(f) => f.read(buf),
+            Self::File(f) => f.read(buf),
+        }
+    }
+}
+
+impl Write for ReadWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::PyFile(f) => f.write(buf),
+            Self::File(f) => f.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::PyFile(f) => f.flush(),
+            Self::File(f) => f.flush(),
+        }
+    }
+}
+
+impl Seek for ReadWriter {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Self::PyFile(f) => f.seek(pos),
+            Self::File(f) => f.seek(pos),
+        }
+    }
+}
+
+impl Truncate for ReadWriter {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        match self {
+            Self::PyFile(f) => f.truncate(len),
+            Self::File(f) => f.truncate(len),
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Checks of the data the appenders add points to, and of the points they are given.
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use pyo3::prelude::*;
+
+use crate::adapters::{ReadWriter, Truncate};
+use crate::chunk_iter::{check_point_count, trimmed_entries};
+use crate::{buffer_length_error, into_py_err, ChunkTable, LazrsError};
+
+/// Checks that the chunk table of `data` agrees with the point data and `point_count`,
+/// so that a wrong `point_count` or a VLR that is not the one of the file is detected
+/// before anything is written.
+///
+/// With fixed size chunks, the number of points of the last chunk is not always
+/// stored, so `point_count` only has to be one that the chunks can hold.
+///
+/// `data` must be positioned at the start of the point data, where it is left.
+pub(crate) fn check_chunk_table<R: Read + Seek>(
+    data: &mut R,
+    vlr: &laz::LazVlr,
+    point_count: u64,
+) -> PyResult<()> {
+    let start = data.stream_position()?;
+    let offset = data.read_i64::<LittleEndian>()?;
+    data.seek(SeekFrom::Start(start))?;
+    let chunk_table = laz::laszip::ChunkTable::read_from(&mut *data, vlr).map_err(into_py_err)?;
+    let entries = chunk_table.as_ref();
+
+    let first_chunk = start + std::mem::size_of::<i64>() as u64;
+    let chunks_end = first_chunk + entries.iter().map(|entry| entry.byte_count).sum::<u64>();
+    // The offset can be invalid if the writer could not update it,
+    // the table was then found at the end of the data
+    if offset > start as i64 && offset as u64 != chunks_end {
+        return Err(PyErr::new::<LazrsError, _>(format!(
+            "The chunks of the chunk table end at {}, but the chunk table is at {}, \
+            the table is corrupt or the VLR is not the one of the file",
+            chunks_end, offset
+        )));
+    }
+    check_point_count(entries, vlr, point_count)?;
+    data.seek(SeekFrom::Start(start))?;
+    Ok(())
+}
+
+/// Checks that the chunks have a variable size if `variable_size`, a fixed size otherwise,
+/// as the parallel appender of laz requires for `compress_chunks` and `compress_many`
+pub(crate) fn check_chunk_kind(vlr: &laz::LazVlr, variable_size: bool) -> PyResult<()> {
+    match (vlr.uses_variable_size_chunks(), variable_size) {
+        (true, false) => Err(PyErr::new::<LazrsError, _>(
+            "The chunks have a variable size, compress_chunks must be used instead of compress_many",
+        )),
+        (false, true) => Err(PyErr::new::<LazrsError, _>(
+            "The chunks have a fixed size, compress_many must be used instead of compress_chunks",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `points` holds whole points, returns their number
+pub(crate) fn num_points(points: &[u8], vlr: &laz::LazVlr) -> PyResult<u64> {
+    let point_size = vlr.items_size() as usize;
+    if !points.len().is_multiple_of(point_size) {
+        return Err(buffer_length_error(
+            format!(
+                "The number of bytes ({}) is not a multiple of the point size ({})",
+                points.len(),
+                point_size
+            ),
+            points.len() - points.len() % point_size,
+            points.len(),
+        ));
+    }
+    Ok((points.len() / point_size) as u64)
+}
+
+/// Reads the chunk table of `data` once the points are appended,
+/// the entry of the last chunk has its real number of points.
+///
+/// The sequential appender of laz counts the bytes of the chunks that were kept
+/// in the size of the first chunk it writes, so the sizes no longer add up
+/// to the offset to the chunk table. The excess is then removed from that chunk,
+/// and the corrected table is written over the one of laz.
+pub(crate) fn appended_chunk_table<W: Read + Write + Seek + Truncate>(
+    data: &mut W,
+    data_start: u64,
+    vlr: &laz::LazVlr,
+    point_count: u64,
+) -> PyResult<ChunkTable> {
+    data.seek(SeekFrom::Start(data_start))?;
+    let offset = data.read_u64::<LittleEndian>()?;
+    data.seek(SeekFrom::Start(data_start))?;
+    let chunk_table = laz::laszip::ChunkTable::read_from(&mut *data, vlr).map_err(into_py_err)?;
+    let mut entries = chunk_table.as_ref().to_vec();
+
+    let first_chunk = data_start + std::mem::size_of::<i64>() as u64;
+    let chunks_size = offset.saturating_sub(first_chunk);
+    let table_size = entries.iter().map(|entry| entry.byte_count).sum::<u64>();
+    if table_size > chunks_size {
+        let excess = table_size - chunks_size;
+        let mut kept_size = 0;
+        let first_written = entries.iter().position(|entry| {
+            let is_first_written = kept_size == excess;
+            kept_size += entry.byte_count;
+            is_first_written
+        });
+        let Some(entry) = first_written
+            .map(|index| &mut entries[index])
+            .filter(|entry| entry.byte_count > excess)
+        else {
+            return Err(PyErr::new::<LazrsError, _>(
+                "The chunk table written by the appender does not match the chunks",
+            ));
+        };
+        entry.byte_count -= excess;
+
+        let mut corrected = laz::laszip::ChunkTable::default();
+        for entry in &entries {
+            corrected.push(*entry);
+        }
+        data.seek(SeekFrom::Start(offset))?;
+        corrected.write_to(&mut *data, vlr)?;
+        let end = data.stream_position()?;
+        data.truncate(end)?;
+    }
+
+    let mut appended = laz::laszip::ChunkTable::default();
+    for entry in trimmed_entries(&entries, point_count) {
+        appended.push(entry);
+    }
+    Ok(ChunkTable::from(appended))
+}
+
+/// Parallel appender.
+///
+/// The parallel appender of laz re-compresses the last chunk with `compress_many`,
+/// which panics for variable-size chunks. As these chunks are always complete,
+/// the compressor then starts after the last chunk instead, and the chunks
+/// that were kept are added back to the chunk table in `done()`.
+pub(crate) enum ParAppender {
+    Fixed(laz::ParLasZipAppender<ReadWriter>),
+    Variable {
+        compressor: laz::ParLasZipCompressor<ReadWriter>,
+        /// Entries of the chunks that were kept
+        kept: laz::laszip::ChunkTable,
+        data_start: u64,
+    },
+}
+
+impl ParAppender {
+    /// `data` must be positioned at the start of the point data
+    pub(crate) fn new(
+        mut data: ReadWriter,
+        vlr: laz::LazVlr,
+        point_count: u64,
+    ) -> laz::Result<Self> {
+        if !vlr.uses_variable_size_chunks() {
+            return laz::ParLasZipAppender::new(data, vlr, point_count).map(Self::Fixed);
+        }
+        let data_start = data.stream_position()?;
+        let mut kept = laz::laszip::ChunkTable::read_from(&mut data, &vlr)?;
+        // The empty chunks after the last point are dropped, as laz does,
+        // the decompressors stop at them
+        while kept
+            .as_ref()
+            .last()
+            .is_some_and(|entry| entry.point_count == 0)
+        {
+            kept.pop();
+        }
+        data.seek(SeekFrom::Start(data_start))?;
+        let mut compressor = laz::ParLasZipCompressor::new(data, vlr)?;
+        compressor.reserve_offset_to_chunk_table()?;
+        let kept_size = kept
+            .as_ref()
+            .iter()
+            .map(|entry| entry.byte_count)
+            .sum::<u64>();
+        compressor
+            .get_mut()
+            .seek(SeekFrom::Current(kept_size as i64))?;
+        Ok(Self::Variable {
+            compressor,
+            kept,
+            data_start,
+        })
+    }
+
+    pub(crate) fn compress_many(&mut self, points: &[u8]) -> std::io::Result<()> {
+        match self {
+            Self::Fixed(appender) => appender.compress_many(points),
+            Self::Variable { compressor, .. } => compressor.compress_many(points),
+        }
+    }
+
+    pub(crate) fn compress_chunks<Item: AsRef<[u8]> + Send>(
+        &mut self,
+        chunks: Vec<Item>,
+    ) -> std::io::Result<()> {
+        match self {
+            Self::Fixed(appender) => appender.compress_chunks(chunks),
+            Self::Variable { compressor, .. } => compressor.compress_chunks(chunks),
+        }
+    }
+
+    pub(crate) fn done(&mut self) -> laz::Result<()> {
+        let (compressor, kept, data_start) = match self {
+            Self::Fixed(appender) => return appender.done(),
+            Self::Variable {
+                compressor,
+                kept,
+                data_start,
+            } => (compressor, kept, *data_start),
+        };
+        compressor.done()?;
+        // The compressor wrote the entries of the chunks it compressed,
+        // they are written again after the ones that were kept
+        let vlr = compressor.vlr().clone();
+        let data = compressor.get_mut();
+        data.seek(SeekFrom::Start(data_start))?;
+        let offset = data.read_u64::<LittleEndian>()?;
+        data.seek(SeekFrom::Start(data_start))?;
+        let mut chunk_table = kept.clone();
+        chunk_table.extend(&laz::laszip::ChunkTable::read_from(&mut *data, &vlr)?);
+        data.seek(SeekFrom::Start(offset))?;
+        chunk_table.write_to(&mut *data, &vlr)?;
+        Ok(())
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut ReadWriter {
+        match self {
+            Self::Fixed(appender) => appender.get_mut(),
+            Self::Variable { compressor, .. } => compressor.get_mut(),
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> ReadWriter {
+        match self {
+            Self::Fixed(appender) => appender.into_inner(),
+            Self::Variable { compressor, .. } => compressor.into_inner(),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Support for returning points as numpy structured arrays.
+//!
+//! numpy is imported at runtime, it is only needed when these
+//! functions are actually used.
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+
+use crate::as_mut_bytes;
+
+/// A field of the point records, as laspy names them
+pub(crate) struct Field {
+    pub(crate) name: &'static str,
+    /// numpy format of the field
+    pub(crate) format: String,
+    /// Offset of the field in the point record
+    pub(crate) offset: usize,
+    pub(crate) size: usize,
+    /// The selection layer the field belongs to,
+    /// `None` when the field is always decompressed
+    layer: Option<u32>,
+}
+
+impl Field {
+    /// Returns whether the field is decompressed when using the selection
+    pub(crate) fn is_selected(&self, selection: laz::DecompressionSelection) -> bool {
+        self.layer.is_none_or(|layer| selection.0 & layer != 0)
+    }
+}
+
+/// Returns the (name, format, layer) of the fields of the item.
+///
+/// Only the items of point formats >= 6 are layered, the fields of
+/// the other items are always decompressed.
+fn item_fields(item: &laz::LazItem) -> Vec<(&'static str, String, Option<u32>)> {
+    use laz::DecompressionSelection as S;
+    let fields: &[(&'static str, &str, Option<u32>)] = match item.item_type() {
+        laz::LazItemType::Point10 => &[
+            ("X", "<i4", None),
+            ("Y", "<i4", None),
+            ("Z", "<i4", None),
+            ("intensity", "<u2", None),
+            ("bit_fields", "u1", None),
+            ("raw_classification", "u1", None),
+            ("scan_angle_rank", "i1", None),
+            ("user_data", "u1", None),
+            ("point_source_id", "<u2", None),
+        ],
+        laz::LazItemType::Point14 => &[
+            ("X", "<i4", None),
+            ("Y", "<i4", None),
+            ("Z", "<i4", Some(S::Z)),
+            ("intensity", "<u2", Some(S::INTENSITY)),
+            ("bit_fields", "u1", None),
+            ("classification_flags", "u1", Some(S::FLAGS)),
+            ("classification", "u1", Some(S::CLASSIFICATION)),
+            ("user_data", "u1", Some(S::USER_DATA)),
+            ("scan_angle", "<i2", Some(S::SCAN_ANGLE)),
+            ("point_source_id", "<u2", Some(S::POINT_SOURCE_ID)),
+            ("gps_time", "<f8", Some(S::GPS_TIME)),
+        ],
+        laz::LazItemType::GpsTime => &[("gps_time", "<f8", None)],
+        laz::LazItemType::RGB12 => &[
+            ("red", "<u2", None),
+            ("green", "<u2", None),
+            ("blue", "<u2", None),
+        ],
+        laz::LazItemType::RGB14 => &[
+            ("red", "<u2", Some(S::RGB)),
+            ("green", "<u2", Some(S::RGB)),
+            ("blue", "<u2", Some(S::RGB)),
+        ],
+        laz::LazItemType::RGBNIR14 => &[
+            ("red", "<u2", Some(S::RGB)),
+            ("green", "<u2", Some(S::RGB)),
+            ("blue", "<u2", Some(S::RGB)),
+            ("nir", "<u2", Some(S::NIR)),
+        ],
+        laz::LazItemType::WavePacket13 => &[
+            ("wavepacket_index", "u1", None),
+            ("wavepacket_offset", "<u8", None),
+            ("wavepacket_size", "<u4", None),
+            ("return_point_wave_location", "<f4", None),
+            ("x_t", "<f4", None),
+            ("y_t", "<f4", None),
+            ("z_t", "<f4", None),
+        ],
+        laz::LazItemType::WavePacket14 => &[
+            ("wavepacket_index", "u1", Some(S::WAVEPACKET)),
+            ("wavepacket_offset", "<u8", Some(S::WAVEPACKET)),
+            ("wavepacket_size", "<u4", Some(S::WAVEPACKET)),
+            ("return_point_wave_location", "<f4", Some(S::WAVEPACKET)),
+            ("x_t", "<f4", Some(S::WAVEPACKET)),
+            ("y_t", "<f4", Some(S::WAVEPACKET)),
+            ("z_t", "<f4", Some(S::WAVEPACKET)),
+        ],
+        laz::LazItemType::Byte(_) => {
+            return vec![("extra_bytes", format!("V{}", item.size()), None)];
+        }
+        laz::LazItemType::Byte14(_) => {
+            return vec![(
+                "extra_bytes",
+                format!("V{}", item.size()),
+                Some(S::ALL_EXTRA_BYTES),
+            )];
+        }
+    };
+    fields
+        .iter()
+        .map(|(name, format, layer)| (*name, format.to_string(), *layer))
+        .collect()
+}
+
+/// Returns the fields of the points described by the vlr
+pub(crate) fn point_fields(vlr: &laz::LazVlr) -> Vec<Field> {
+    let mut offset = 0;
+    let mut fields = Vec::new();
+    for item in vlr.items() {
+        for (name, format, layer) in item_fields(item) {
+            // The size is the number at the end of the format, e.g. 4 for '<i4'
+            let size = format
+                .trim_start_matches(|c: char| !c.is_ascii_digit())
+                .parse::<usize>()
+                .unwrap();
+            fields.push(Field {
+                name,
+                format,
+                offset,
+                size,
+                layer,
+            });
+            offset += size;
+        }
+    }
+    fields
+}
+
+/// Returns the numpy structured dtype of the points described by the vlr
+pub(crate) fn points_dtype<'py>(py: Python<'py>, vlr: &laz::LazVlr) -> PyResult<Bound<'py, PyAny>> {
+    let numpy = py.import("numpy")?;
+    let fields = PyList::empty(py);
+    for field in point_fields(vlr) {
+        fields.append((field.name, field.format))?;
+    }
+    numpy.call_method1("dtype", (fields,))
+}
+
+/// Allocates an array of `num_points` points, and lets `fill` write the points' bytes in it.
+pub(crate) fn new_points_array<'py, F>(
+    py: Python<'py>,
+    vlr: &laz::LazVlr,
+    num_points: usize,
+    fill: F,
+) -> PyResult<Bound<'py, PyAny>>
+where
+    F: FnOnce(&mut [u8]) -> PyResult<()>,
+{
+    let numpy = py.import("numpy")?;
+    let dtype = points_dtype(py, vlr)?;
+    // Structured arrays can't be accessed as a buffer of u8,
+    // so we fill a u8 array, and return a view of it with the point dtype
+    let num_bytes = num_points * vlr.items_size() as usize;
+    let raw = numpy.call_method1("zeros", (num_bytes, numpy.getattr("uint8")?))?;
+    fill(&mut as_mut_bytes(&raw)?)?;
+    raw.call_method1("view", (dtype,))
+}
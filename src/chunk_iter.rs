@@ -0,0 +1,288 @@
+//! Iteration over the decompressed chunks of a LAZ source.
+use std::collections::VecDeque;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::{mpsc, Mutex};
+
+use laz::laszip::ChunkTableEntry;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rayon::prelude::*;
+
+use crate::threads::Pool;
+use crate::{into_py_err, LasZipDecompressor, LazrsError, ParLasZipDecompressor};
+
+/// Returns the entries of the chunks that hold the `point_count` first points,
+/// without the empty chunks at the end of the table.
+///
+/// With fixed size chunks, the last entry of the table says the chunk is full,
+/// `point_count` gives the real number of points of the last chunk
+/// (see `recovery::table_point_count`).
+pub(crate) fn trimmed_entries(
+    entries: &[ChunkTableEntry],
+    point_count: u64,
+) -> Vec<ChunkTableEntry> {
+    let mut num_points = 0u64;
+    entries
+        .iter()
+        .map_while(|entry| {
+            if num_points == point_count {
+                return None;
+            }
+            let count = entry.point_count.min(point_count - num_points);
+            num_points += count;
+            Some(ChunkTableEntry {
+                point_count: count,
+                byte_count: entry.byte_count,
+            })
+        })
+        .collect()
+}
+
+/// Checks that the chunks of the table can hold `point_count` points.
+///
+/// With fixed size chunks, all the chunks but the last are full, and the last one
+/// has at least one point, with variable size chunks, the entries add up to `point_count`.
+pub(crate) fn check_point_count(
+    entries: &[ChunkTableEntry],
+    vlr: &laz::LazVlr,
+    point_count: u64,
+) -> PyResult<()> {
+    let counts = if vlr.uses_variable_size_chunks() {
+        let total = entries
+            .iter()
+            .fold(0u64, |total, entry| total.saturating_add(entry.point_count));
+        total..=total
+    } else {
+        let chunk_size = u64::from(vlr.chunk_size());
+        match (entries.len() as u64).checked_sub(1) {
+            Some(num_full) => num_full * chunk_size + 1..=(num_full + 1) * chunk_size,
+            None => 0..=0,
+        }
+    };
+    if counts.contains(&point_count) {
+        return Ok(());
+    }
+    let counts = if counts.start() == counts.end() {
+        counts.start().to_string()
+    } else {
+        format!("{} to {}", counts.start(), counts.end())
+    };
+    Err(PyErr::new::<LazrsError, _>(format!(
+        "The {} chunks of the chunk table hold {} points, not {}",
+        entries.len(),
+        counts,
+        point_count
+    )))
+}
+
+/// Iterator over the chunks of a decompressor, yields the points of each chunk as bytes
+#[pyclass]
+pub(crate) struct ChunkIterator {
+    chunks: Chunks,
+}
+
+enum Chunks {
+    Sequential {
+        decompressor: Py<LasZipDecompressor>,
+        entries: std::vec::IntoIter<ChunkTableEntry>,
+    },
+    Parallel(Prefetcher),
+}
+
+impl ChunkIterator {
+    pub(crate) fn sequential(
+        decompressor: &Bound<'_, LasZipDecompressor>,
+        point_count: Option<u64>,
+    ) -> PyResult<Self> {
+        let mut this = decompressor.borrow_mut();
+        let this = &mut *this;
+        let vlr = this.decompressor.vlr().clone();
+        let source = this.decompressor.get_mut();
+        let point_count = match point_count {
+            Some(point_count) => point_count,
+            None => this.ranges.point_count(source, &vlr)?,
+        };
+        let chunk_table = this.ranges.chunk_table(source, &vlr)?;
+        let entries = trimmed_entries(chunk_table.as_ref(), point_count);
+        this.decompressor.seek(0).map_err(into_py_err)?;
+        Ok(Self {
+            chunks: Chunks::Sequential {
+                decompressor: decompressor.clone().unbind(),
+                entries: entries.into_iter(),
+            },
+        })
+    }
+
+    pub(crate) fn parallel(
+        decompressor: &Bound<'_, ParLasZipDecompressor>,
+        point_count: Option<u64>,
+        prefetch: Option<usize>,
+    ) -> PyResult<Self> {
+        let mut this = decompressor.borrow_mut();
+        let this = &mut *this;
+        let source = this.decompressor.get_mut();
+        let point_count = match point_count {
+            Some(point_count) => point_count,
+            None => this.ranges.point_count(source, &this.vlr)?,
+        };
+        let chunk_table = this.ranges.chunk_table(source, &this.vlr)?;
+        let entries = trimmed_entries(chunk_table.as_ref(), point_count);
+        let batch_len = prefetch.unwrap_or_else(|| this.pool.install(rayon::current_num_threads));
+        Ok(Self {
+            chunks: Chunks::Parallel(Prefetcher {
+                decompressor: decompressor.clone().unbind(),
+                vlr: this.vlr.clone(),
+                selection: this.selection,
+                pool: this.pool.clone(),
+                entries,
+                next_entry: 0,
+                position: this.ranges.first_chunk_position(),
+                batch_len: batch_len.max(1),
+                ready: VecDeque::new(),
+                pending: None,
+            }),
+        })
+    }
+}
+
+#[pymethods]
+impl ChunkIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        match &mut self.chunks {
+            Chunks::Sequential {
+                decompressor,
+                entries,
+            } => {
+                let Some(entry) = entries.next() else {
+                    return Ok(None);
+                };
+                let mut decompressor = decompressor.borrow_mut(py);
+                let decompressor = &mut decompressor.decompressor;
+                let num_bytes = entry.point_count * decompressor.vlr().items_size();
+                PyBytes::new_with(py, num_bytes as usize, |points| {
+                    py.detach(|| decompressor.decompress_many(points))
+                        .map_err(into_py_err)
+                })
+                .map(Some)
+            }
+            Chunks::Parallel(prefetcher) => prefetcher
+                .next(py)
+                .map(|points| points.map(|points| PyBytes::new(py, &points))),
+        }
+    }
+}
+
+type Batch = laz::Result<Vec<Vec<u8>>>;
+
+/// Decompresses the chunks in batches of `batch_len` chunks, in parallel,
+/// the next batch is decompressed in the background while the current one is consumed.
+struct Prefetcher {
+    decompressor: Py<ParLasZipDecompressor>,
+    vlr: laz::LazVlr,
+    selection: laz::DecompressionSelection,
+    pool: Pool,
+    entries: Vec<ChunkTableEntry>,
+    /// Index of the next chunk to read from the source
+    next_entry: usize,
+    /// Position of the next chunk to read in the source
+    position: u64,
+    batch_len: usize,
+    /// The decompressed chunks, not yet consumed
+    ready: VecDeque<Vec<u8>>,
+    /// The batch being decompressed in the background
+    /// (in a mutex, only because the receiver is not `Sync`)
+    pending: Option<Mutex<mpsc::Receiver<Batch>>>,
+}
+
+impl Prefetcher {
+    /// Reads the compressed data of the next batch of chunks
+    fn read_batch(&mut self, py: Python) -> PyResult<Option<(Vec<u8>, Vec<ChunkTableEntry>)>> {
+        if self.next_entry == self.entries.len() {
+            return Ok(None);
+        }
+        let end = (self.next_entry + self.batch_len).min(self.entries.len());
+        let entries = self.entries[self.next_entry..end].to_vec();
+        let num_bytes = entries.iter().map(|entry| entry.byte_count).sum::<u64>();
+
+        let mut compressed = vec![0u8; num_bytes as usize];
+        let mut decompressor = self.decompressor.borrow_mut(py);
+        let source = decompressor.decompressor.get_mut();
+        source.seek(SeekFrom::Start(self.position))?;
+        source.read_exact(&mut compressed)?;
+
+        self.next_entry = end;
+        self.position += num_bytes;
+        Ok(Some((compressed, entries)))
+    }
+
+    /// Starts decompressing the next batch in the background
+    fn prefetch(&mut self, py: Python) -> PyResult<()> {
+        let Some((compressed, entries)) = self.read_batch(py)? else {
+            return Ok(());
+        };
+        let (sender, receiver) = mpsc::channel();
+        let vlr = self.vlr.clone();
+        let selection = self.selection;
+        self.pool.spawn(move || {
+            // The iterator may have been dropped, so the result is not needed
+            let _ = sender.send(decompress_batch(&compressed, &vlr, &entries, selection));
+        });
+        self.pending = Some(Mutex::new(receiver));
+        Ok(())
+    }
+
+    fn next(&mut self, py: Python) -> PyResult<Option<Vec<u8>>> {
+        if self.ready.is_empty() {
+            if self.pending.is_none() {
+                self.prefetch(py)?;
+            }
+            let Some(pending) = self.pending.take() else {
+                // All the chunks were consumed, position the decompressor at the end
+                let num_points = self.entries.iter().map(|entry| entry.point_count).sum();
+                let mut decompressor = self.decompressor.borrow_mut(py);
+                return decompressor
+                    .decompressor
+                    .seek(num_points)
+                    .map(|_| None)
+                    .map_err(into_py_err);
+            };
+            let batch = py
+                .detach(move || pending.into_inner().unwrap().recv())
+                .map_err(|_| {
+                    PyErr::new::<LazrsError, _>("The decompression of the chunks stopped")
+                })?;
+            self.ready.extend(batch.map_err(into_py_err)?);
+            self.prefetch(py)?;
+        }
+        Ok(self.ready.pop_front())
+    }
+}
+
+/// Decompresses each chunk in its own buffer
+fn decompress_batch(
+    compressed: &[u8],
+    vlr: &laz::LazVlr,
+    entries: &[ChunkTableEntry],
+    selection: laz::DecompressionSelection,
+) -> Batch {
+    let mut chunks = Vec::with_capacity(entries.len());
+    let mut rest = compressed;
+    for entry in entries {
+        let (chunk, r) = rest.split_at(entry.byte_count as usize);
+        chunks.push((chunk, *entry));
+        rest = r;
+    }
+    let point_size = vlr.items_size();
+    chunks
+        .into_par_iter()
+        .map(|(chunk, entry)| {
+            let mut points = vec![0u8; (entry.point_count * point_size) as usize];
+            laz::par_decompress_selective(chunk, &mut points, vlr, &[entry], selection)?;
+            Ok(points)
+        })
+        .collect()
+}
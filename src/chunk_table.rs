@@ -0,0 +1,206 @@
+use std::os::raw::c_int;
+
+use pyo3::exceptions::{PyBufferError, PyIndexError};
+use pyo3::ffi;
+use pyo3::prelude::*;
+use pyo3::types::{PyIterator, PyList};
+
+/// The chunk table of a LAZ file.
+///
+/// Each entry is a `(point_count, byte_count)` tuple.
+///
+/// The table supports the buffer protocol, it is exposed as a
+/// `(len(table), 2)` array of u64, so `numpy.asarray(table)` works.
+#[pyclass(from_py_object)]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChunkTable {
+    pub(crate) table: laz::laszip::ChunkTable,
+}
+
+impl From<laz::laszip::ChunkTable> for ChunkTable {
+    fn from(table: laz::laszip::ChunkTable) -> Self {
+        Self { table }
+    }
+}
+
+impl ChunkTable {
+    fn entries(&self) -> &[laz::laszip::ChunkTableEntry] {
+        self.table.as_ref()
+    }
+
+    /// Index of the first point of each chunk
+    pub(crate) fn point_offsets(&self) -> Vec<u64> {
+        cumulative_sum(self.entries().iter().map(|entry| entry.point_count))
+    }
+
+    fn check_index(&self, index: isize) -> PyResult<usize> {
+        let len = self.entries().len() as isize;
+        let index = if index < 0 { index + len } else { index };
+        if (0..len).contains(&index) {
+            Ok(index as usize)
+        } else {
+            Err(PyIndexError::new_err("chunk table index out of range"))
+        }
+    }
+}
+
+/// Returns the cumulative sum of the values, starting at 0
+/// (the last value is not part of the result).
+fn cumulative_sum(values: impl Iterator<Item = u64>) -> Vec<u64> {
+    values
+        .scan(0u64, |sum, value| {
+            let start = *sum;
+            *sum += value;
+            Some(start)
+        })
+        .collect()
+}
+
+/// Data exported via the buffer protocol, it lives until the buffer is released
+struct BufferData {
+    values: Vec<u64>,
+    shape: [ffi::Py_ssize_t; 2],
+    strides: [ffi::Py_ssize_t; 2],
+}
+
+#[pymethods]
+impl ChunkTable {
+    /// Creates a chunk table from an iterable of `(point_count, byte_count)`
+    #[new]
+    #[pyo3(signature = (entries = None))]
+    fn new(entries: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let mut table = laz::laszip::ChunkTable::default();
+        if let Some(entries) = entries {
+            for entry in entries.try_iter()? {
+                let (point_count, byte_count): (u64, u64) = entry?.extract()?;
+                table.push(laz::laszip::ChunkTableEntry {
+                    point_count,
+                    byte_count,
+                });
+            }
+        }
+        Ok(Self { table })
+    }
+
+    fn __len__(&self) -> usize {
+        self.entries().len()
+    }
+
+    fn __getitem__(&self, index: isize) -> PyResult<(u64, u64)> {
+        let entry = self.entries()[self.check_index(index)?];
+        Ok((entry.point_count, entry.byte_count))
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        let entries = self
+            .entries()
+            .iter()
+            .map(|entry| (entry.point_count, entry.byte_count));
+        PyList::new(py, entries)?.try_iter()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<ChunkTable(chunks: {}, points: {})>",
+            self.entries().len(),
+            self.total_points()
+        )
+    }
+
+    /// Total number of points in the chunks.
+    ///
+    /// With fixed size chunks, the last entry of the table says the chunk is full,
+    /// so this is an upper bound, the point count of the LAS header is the real total.
+    #[getter]
+    fn total_points(&self) -> u64 {
+        self.entries().iter().map(|entry| entry.point_count).sum()
+    }
+
+    /// Offset of each chunk, relative to the start of the first chunk
+    #[getter]
+    fn byte_offsets(&self) -> Vec<u64> {
+        cumulative_sum(self.entries().iter().map(|entry| entry.byte_count))
+    }
+
+    /// Returns the index of the chunk which contains the point at `point_index`.
+    ///
+    /// `point_count` is the real number of points (the point count of the LAS header),
+    /// with fixed size chunks the last entry of the table may count points past it.
+    #[pyo3(signature = (point_index, point_count = None))]
+    fn chunk_index_for_point(&self, point_index: u64, point_count: Option<u64>) -> PyResult<usize> {
+        let total_points = self.total_points();
+        let num_points = point_count.map_or(total_points, |count| count.min(total_points));
+        if point_index >= num_points {
+            return Err(PyIndexError::new_err(format!(
+                "point index {} is out of range",
+                point_index
+            )));
+        }
+        let offsets = self.point_offsets();
+        Ok(offsets.partition_point(|&offset| offset <= point_index) - 1)
+    }
+
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("ChunkTable is not writable"));
+        }
+
+        let values = slf
+            .borrow()
+            .entries()
+            .iter()
+            .flat_map(|entry| [entry.point_count, entry.byte_count])
+            .collect::<Vec<u64>>();
+        let item_size = std::mem::size_of::<u64>() as ffi::Py_ssize_t;
+        let data = Box::new(BufferData {
+            shape: [(values.len() / 2) as ffi::Py_ssize_t, 2],
+            strides: [2 * item_size, item_size],
+            values,
+        });
+
+        unsafe {
+            (*view).obj = slf.into_any().into_ptr();
+            (*view).buf = data.values.as_ptr() as *mut std::os::raw::c_void;
+            (*view).len = data.values.len() as ffi::Py_ssize_t * item_size;
+            (*view).readonly = 1;
+            (*view).itemsize = item_size;
+            (*view).format = if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+                c"Q".as_ptr() as *mut _
+            } else {
+                std::ptr::null_mut()
+            };
+            if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+                (*view).ndim = 2;
+                (*view).shape = data.shape.as_ptr() as *mut _;
+            } else {
+                (*view).ndim = 1;
+                (*view).shape = std::ptr::null_mut();
+            }
+            (*view).strides = if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+                data.strides.as_ptr() as *mut _
+            } else {
+                std::ptr::null_mut()
+            };
+            (*view).suboffsets = std::ptr::null_mut();
+            (*view).internal = Box::into_raw(data) as *mut std::os::raw::c_void;
+        }
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, view: *mut ffi::Py_buffer) {
+        drop(unsafe { Box::from_raw((*view).internal as *mut BufferData) });
+    }
+}
+
+/// Gets the chunk table from either a `ChunkTable`
+/// or a list of `(point_count, byte_count)` tuples.
+pub(crate) fn chunk_table_from_py(object: &Bound<'_, PyAny>) -> PyResult<laz::laszip::ChunkTable> {
+    if let Ok(chunk_table) = object.cast::<ChunkTable>() {
+        return Ok(chunk_table.borrow().table.clone());
+    }
+    ChunkTable::new(Some(object)).map(|chunk_table| chunk_table.table)
+}
@@ -0,0 +1,190 @@
+//! Decompression of points into one buffer per field (struct of arrays)
+//! instead of one buffer of interleaved point records.
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString};
+
+use crate::arrays::{point_fields, Field};
+use crate::{as_mut_bytes, buffer_length_error, BytesMut, LazrsError};
+
+/// Number of points decompressed at once before being scattered in the columns
+const BATCH_SIZE: usize = 50_000;
+
+/// Checks that no two buffers share bytes, as they are all written to
+fn check_no_overlap(columns: &[(Field, BytesMut)]) -> PyResult<()> {
+    let mut ranges = columns
+        .iter()
+        .filter(|(_, buffer)| !buffer.is_empty())
+        .map(|(field, buffer)| (buffer.as_ptr_range(), field.name))
+        .collect::<Vec<_>>();
+    ranges.sort_by_key(|(range, _)| range.start);
+    // Sorted by their start, a buffer that overlaps another overlaps the next one
+    for ((previous, previous_name), (next, next_name)) in ranges.iter().zip(ranges.iter().skip(1)) {
+        if next.start < previous.end {
+            return Err(PyErr::new::<LazrsError, _>(format!(
+                "The buffers for '{}' and '{}' overlap",
+                previous_name, next_name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The caller's buffers, one per field
+pub(crate) struct Columns {
+    point_size: usize,
+    num_points: usize,
+    columns: Vec<(Field, BytesMut)>,
+}
+
+impl Columns {
+    /// Gets the buffers from the dict which maps field names to writable buffers.
+    ///
+    /// Only the fields that are decompressed with the selection can be requested,
+    /// and all the buffers must be able to hold the same number of points.
+    pub(crate) fn from_dict(
+        vlr: &laz::LazVlr,
+        selection: laz::DecompressionSelection,
+        dict: &Bound<'_, PyDict>,
+    ) -> PyResult<Self> {
+        let mut fields = point_fields(vlr);
+        let mut columns = Vec::with_capacity(dict.len());
+        let mut num_points = None;
+        for (name, buffer) in dict.iter() {
+            let name = name.cast::<PyString>()?.to_cow()?;
+            let index = fields
+                .iter()
+                .position(|field| field.name == name)
+                .ok_or_else(|| {
+                    PyErr::new::<LazrsError, _>(format!("The points have no field '{}'", name))
+                })?;
+            let field = fields.swap_remove(index);
+            if !field.is_selected(selection) {
+                return Err(PyErr::new::<LazrsError, _>(format!(
+                    "The field '{}' is not decompressed with the selection",
+                    name
+                )));
+            }
+
+            let buffer = as_mut_bytes(&buffer)?;
+            if buffer.len() % field.size != 0 {
+                return Err(buffer_length_error(
+                    format!(
+                        "The size of the buffer for '{}' ({}) is not a multiple of the field size ({})",
+                        name,
+                        buffer.len(),
+                        field.size
+                    ),
+                    buffer.len() - buffer.len() % field.size,
+                    buffer.len(),
+                ));
+            }
+            let count = buffer.len() / field.size;
+            let expected_count = *num_points.get_or_insert(count);
+            if expected_count != count {
+                return Err(buffer_length_error(
+                    format!(
+                        "The buffer for '{}' holds {} points, the other buffers hold {} points",
+                        name, count, expected_count
+                    ),
+                    expected_count * field.size,
+                    buffer.len(),
+                ));
+            }
+            columns.push((field, buffer));
+        }
+        check_no_overlap(&columns)?;
+
+        Ok(Self {
+            point_size: vlr.items_size() as usize,
+            num_points: num_points.unwrap_or(0),
+            columns,
+        })
+    }
+
+    /// Number of points the columns can hold
+    pub(crate) fn num_points(&self) -> usize {
+        self.num_points
+    }
+
+    /// Copies the fields of the `points` records in the columns, starting at point `start`
+    fn scatter(&mut self, start: usize, points: &[u8]) {
+        for (field, column) in &mut self.columns {
+            let (offset, size) = (field.offset, field.size);
+            let column = &mut column[start * size..];
+            for (point, value) in points
+                .chunks_exact(self.point_size)
+                .zip(column.chunks_exact_mut(size))
+            {
+                value.copy_from_slice(&point[offset..offset + size]);
+            }
+        }
+    }
+
+    /// Fills the columns with points, `decompress` is called with
+    /// buffers of interleaved point records to fill.
+    pub(crate) fn decompress_with<E, F>(&mut self, mut decompress: F) -> Result<(), E>
+    where
+        F: FnMut(&mut [u8]) -> Result<(), E>,
+    {
+        let mut points = vec![0u8; BATCH_SIZE.min(self.num_points) * self.point_size];
+        let mut start = 0;
+        while start < self.num_points {
+            let count = BATCH_SIZE.min(self.num_points - start);
+            let points = &mut points[..count * self.point_size];
+            decompress(points)?;
+            self.scatter(start, points);
+            start += count;
+        }
+        Ok(())
+    }
+
+    /// Fills the columns with the points of the chunks, chunks are decompressed in parallel.
+    ///
+    /// `compressed_points` must start at the first chunk of the table.
+    pub(crate) fn par_decompress_chunks(
+        &mut self,
+        compressed_points: &[u8],
+        vlr: &laz::LazVlr,
+        chunk_table: &[laz::laszip::ChunkTableEntry],
+        selection: laz::DecompressionSelection,
+    ) -> laz::Result<()> {
+        // Decompress as many chunks as there are threads at once,
+        // so that the temporary buffer does not hold all the points
+        let batch_len = rayon::current_num_threads().max(1);
+
+        let mut compressed_start = 0;
+        let mut start = 0;
+        let mut points = Vec::new();
+        for batch in chunk_table.chunks(batch_len) {
+            if start >= self.num_points {
+                break;
+            }
+            // The last chunk may hold less points than its entry says
+            let mut entries = batch.to_vec();
+            let mut count = 0;
+            for entry in &mut entries {
+                entry.point_count = entry
+                    .point_count
+                    .min((self.num_points - start - count) as u64);
+                count += entry.point_count as usize;
+            }
+            let compressed_len = batch
+                .iter()
+                .map(|entry| entry.byte_count as usize)
+                .sum::<usize>();
+            let compressed =
+                compressed_points.get(compressed_start..compressed_start + compressed_len);
+            let Some(compressed) = compressed else {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            };
+
+            points.resize(count * self.point_size, 0u8);
+            laz::par_decompress_selective(compressed, &mut points, vlr, &entries, selection)?;
+            self.scatter(start, &points);
+
+            compressed_start += compressed_len;
+            start += count;
+        }
+        Ok(())
+    }
+}
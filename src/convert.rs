@@ -0,0 +1,263 @@
+//! Conversion of point records between the point formats 0 to 5 (based on Point10)
+//! and 6 to 10 (based on Point14), before their compression.
+use std::collections::BTreeMap;
+
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::arrays::point_fields;
+use crate::{as_bytes, buffer_length_error, into_py_err, LazVlr, LazrsError, ParLasZipCompressor};
+
+/// Unit of the scan angle of the point formats >= 6, in degrees
+const SCAN_ANGLE_UNIT: f64 = 0.006;
+/// Range of the scan angle rank of the point formats < 6, in degrees
+const SCAN_ANGLE_RANK_RANGE: std::ops::RangeInclusive<f64> = -90.0..=90.0;
+
+/// Offsets of the fields that differ between the Point10 and Point14 records
+const BIT_FIELDS: usize = 14;
+const LEGACY_CLASSIFICATION: usize = 15;
+const SCAN_ANGLE_RANK: usize = 16;
+const CLASSIFICATION_FLAGS: usize = 15;
+const CLASSIFICATION: usize = 16;
+const SCAN_ANGLE: usize = 18;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Family {
+    Legacy,
+    Extended,
+}
+
+impl Family {
+    fn of(point_format_id: u8) -> Self {
+        if point_format_id >= 6 {
+            Self::Extended
+        } else {
+            Self::Legacy
+        }
+    }
+}
+
+/// Copy of the bytes of a field, from the source record to the destination record
+struct FieldCopy {
+    src_offset: usize,
+    dst_offset: usize,
+    len: usize,
+}
+
+/// Counts of the values that could not be represented in the destination format,
+/// by field name
+type Unrepresentable = BTreeMap<&'static str, u64>;
+
+/// Converts the fields of the Point10 part to the Point14 part
+fn legacy_to_extended(src: &[u8], dst: &mut [u8]) {
+    let bit_fields = src[BIT_FIELDS];
+    let classification = src[LEGACY_CLASSIFICATION];
+    let return_number = bit_fields & 0b111;
+    let number_of_returns = (bit_fields >> 3) & 0b111;
+    dst[BIT_FIELDS] = return_number | (number_of_returns << 4);
+    // synthetic, key-point and withheld, then the scan direction and edge of flight line,
+    // the overlap bit and the scanner channel are 0
+    dst[CLASSIFICATION_FLAGS] = (classification >> 5) | (bit_fields & 0b1100_0000);
+    dst[CLASSIFICATION] = classification & 0b1_1111;
+    let scan_angle_rank = src[SCAN_ANGLE_RANK] as i8;
+    let scan_angle = (f64::from(scan_angle_rank) / SCAN_ANGLE_UNIT).round() as i16;
+    dst[SCAN_ANGLE..SCAN_ANGLE + 2].copy_from_slice(&scan_angle.to_le_bytes());
+}
+
+/// Converts the fields of the Point14 part to the Point10 part,
+/// the values that cannot be represented are clamped
+fn extended_to_legacy(src: &[u8], dst: &mut [u8], unrepresentable: &mut Unrepresentable) {
+    let mut clamp = |name: &'static str, value: u8, max: u8| {
+        if value > max {
+            *unrepresentable.entry(name).or_default() += 1;
+        }
+        value.min(max)
+    };
+    let bit_fields = src[BIT_FIELDS];
+    let flags = src[CLASSIFICATION_FLAGS];
+    let return_number = clamp("return_number", bit_fields & 0b1111, 0b111);
+    let number_of_returns = clamp("number_of_returns", bit_fields >> 4, 0b111);
+    let classification = clamp("classification", src[CLASSIFICATION], 0b1_1111);
+    clamp("overlap", (flags >> 3) & 0b1, 0);
+    clamp("scanner_channel", (flags >> 4) & 0b11, 0);
+    dst[BIT_FIELDS] = return_number | (number_of_returns << 3) | (flags & 0b1100_0000);
+    dst[LEGACY_CLASSIFICATION] = classification | ((flags & 0b111) << 5);
+
+    let scan_angle = i16::from_le_bytes([src[SCAN_ANGLE], src[SCAN_ANGLE + 1]]);
+    let mut scan_angle_rank = (f64::from(scan_angle) * SCAN_ANGLE_UNIT).round();
+    if !SCAN_ANGLE_RANK_RANGE.contains(&scan_angle_rank) {
+        *unrepresentable.entry("scan_angle").or_default() += 1;
+        scan_angle_rank =
+            scan_angle_rank.clamp(*SCAN_ANGLE_RANK_RANGE.start(), *SCAN_ANGLE_RANK_RANGE.end());
+    }
+    dst[SCAN_ANGLE_RANK] = (scan_angle_rank as i8) as u8;
+}
+
+/// Converts point records from a point format to another, to compress them.
+///
+/// The fields both formats have are copied, the fields of the destination
+/// that the source does not have (e.g. the GPS time, RGB or NIR) are 0.
+///
+/// Between the point formats 0 to 5 and 6 to 10, the return numbers,
+/// the classification and its flags, and the scan angle are converted.
+/// The values that cannot be represented in the destination format are clamped,
+/// and counted in `unrepresentable`: return numbers or classifications that do not
+/// fit in their fewer bits, the overlap flag, the scanner channel,
+/// and scan angles outside of -90 to 90 degrees.
+#[pyclass]
+pub(crate) struct PointConverter {
+    src_vlr: laz::LazVlr,
+    dst_vlr: laz::LazVlr,
+    copies: Vec<FieldCopy>,
+    families: (Family, Family),
+    unrepresentable: Unrepresentable,
+}
+
+impl PointConverter {
+    fn convert_into(&mut self, src: &[u8], dst: &mut [u8]) {
+        let src_point_size = self.src_vlr.items_size() as usize;
+        let dst_point_size = self.dst_vlr.items_size() as usize;
+        for (src_point, dst_point) in src
+            .chunks_exact(src_point_size)
+            .zip(dst.chunks_exact_mut(dst_point_size))
+        {
+            for copy in &self.copies {
+                dst_point[copy.dst_offset..copy.dst_offset + copy.len]
+                    .copy_from_slice(&src_point[copy.src_offset..copy.src_offset + copy.len]);
+            }
+            match self.families {
+                (Family::Legacy, Family::Extended) => legacy_to_extended(src_point, dst_point),
+                (Family::Extended, Family::Legacy) => {
+                    extended_to_legacy(src_point, dst_point, &mut self.unrepresentable)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_length(&self, points: &[u8]) -> PyResult<()> {
+        let point_size = self.src_vlr.items_size() as usize;
+        if !points.len().is_multiple_of(point_size) {
+            return Err(buffer_length_error(
+                format!(
+                    "The number of bytes ({}) is not a multiple of the point size ({})",
+                    points.len(),
+                    point_size
+                ),
+                points.len() - points.len() % point_size,
+                points.len(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn converted_len(&self, points: &[u8]) -> usize {
+        points.len() / self.src_vlr.items_size() as usize * self.dst_vlr.items_size() as usize
+    }
+}
+
+#[pymethods]
+impl PointConverter {
+    /// Both point formats have `num_extra_bytes` extra bytes
+    #[new]
+    #[pyo3(signature = (src_point_format, dst_point_format, num_extra_bytes = 0))]
+    fn new(src_point_format: u8, dst_point_format: u8, num_extra_bytes: u16) -> PyResult<Self> {
+        let vlr = |point_format_id| {
+            laz::LazVlrBuilder::default()
+                .with_point_format(point_format_id, num_extra_bytes)
+                .map(laz::LazVlrBuilder::build)
+                .map_err(into_py_err)
+        };
+        let src_vlr = vlr(src_point_format)?;
+        let dst_vlr = vlr(dst_point_format)?;
+        let families = (Family::of(src_point_format), Family::of(dst_point_format));
+
+        let src_fields = point_fields(&src_vlr);
+        let copies = point_fields(&dst_vlr)
+            .into_iter()
+            .filter_map(|dst_field| {
+                // The bit fields have the same name, but not the same layout
+                if families.0 != families.1 && dst_field.name == "bit_fields" {
+                    return None;
+                }
+                src_fields
+                    .iter()
+                    .find(|src_field| {
+                        src_field.name == dst_field.name && src_field.format == dst_field.format
+                    })
+                    .map(|src_field| FieldCopy {
+                        src_offset: src_field.offset,
+                        dst_offset: dst_field.offset,
+                        len: dst_field.size,
+                    })
+            })
+            .collect();
+
+        Ok(Self {
+            src_vlr,
+            dst_vlr,
+            copies,
+            families,
+            unrepresentable: Unrepresentable::new(),
+        })
+    }
+
+    /// The LazVlr of the converted points
+    fn dst_vlr(&self) -> LazVlr {
+        LazVlr {
+            vlr: self.dst_vlr.clone(),
+        }
+    }
+
+    /// The number of values that could not be represented, by field name,
+    /// of all the points converted so far
+    #[getter]
+    fn unrepresentable(&self) -> Unrepresentable {
+        self.unrepresentable.clone()
+    }
+
+    /// Returns the converted points
+    fn convert<'py>(
+        &mut self,
+        py: Python<'py>,
+        points: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let points = as_bytes(points)?;
+        self.check_length(&points)?;
+        PyBytes::new_with(py, self.converted_len(&points), |converted| {
+            py.detach(|| self.convert_into(&points, converted));
+            Ok(())
+        })
+    }
+
+    /// Converts the points and compresses them with the compressor,
+    /// which must use the items of `dst_vlr()` and fixed-size chunks.
+    fn compress_many<'py>(
+        &mut self,
+        py: Python<'py>,
+        compressor: &Bound<'py, ParLasZipCompressor>,
+        points: &Bound<'py, PyAny>,
+    ) -> PyResult<()> {
+        let points = as_bytes(points)?;
+        self.check_length(&points)?;
+        let mut compressor = compressor.borrow_mut();
+        let pool = compressor.pool.clone();
+        let compressor = compressor.compressor()?;
+        if compressor.vlr().items() != self.dst_vlr.items() {
+            return Err(PyErr::new::<LazrsError, _>(
+                "The compressor does not compress the converted point format",
+            ));
+        }
+        if compressor.vlr().uses_variable_size_chunks() {
+            return Err(PyErr::new::<LazrsError, _>(
+                "The compressor uses variable-size chunks, use compress_chunks with the converted points",
+            ));
+        }
+        py.detach(|| {
+            let mut converted = vec![0u8; self.converted_len(&points)];
+            self.convert_into(&points, &mut converted);
+            pool.install(|| compressor.compress_many(&converted))
+        })?;
+        Ok(())
+    }
+}
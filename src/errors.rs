@@ -0,0 +1,147 @@
+//! The exceptions raised by lazrs.
+//!
+//! Each variant of `laz::LasZipError` has its own subclass of `LazrsError`,
+//! the values the variant holds are set as attributes of the exception.
+use pyo3::create_exception;
+use pyo3::prelude::*;
+
+create_exception!(lazrs, LazrsError, pyo3::exceptions::PyRuntimeError);
+create_exception!(lazrs, UnknownLazItemError, LazrsError);
+create_exception!(lazrs, UnsupportedLazItemVersionError, LazrsError);
+create_exception!(lazrs, UnknownCompressorTypeError, LazrsError);
+create_exception!(lazrs, UnsupportedCompressorTypeError, LazrsError);
+create_exception!(lazrs, UnsupportedPointFormatError, LazrsError);
+create_exception!(lazrs, MissingChunkTableError, LazrsError);
+create_exception!(lazrs, LazrsIoError, LazrsError);
+create_exception!(lazrs, BufferLengthError, LazrsError);
+
+/// Creates the exception, with the given attributes set on it
+fn new_err<'py, T: pyo3::PyTypeInfo>(
+    py: Python<'py>,
+    message: String,
+    attributes: &[(&str, Bound<'py, PyAny>)],
+) -> PyErr {
+    let err = PyErr::new::<T, _>(message);
+    let value = err.value(py);
+    for (name, attribute) in attributes {
+        if let Err(e) = value.setattr(*name, attribute) {
+            return e;
+        }
+    }
+    err
+}
+
+/// Errors that can be converted to the matching lazrs exception
+pub(crate) trait IntoPyErr {
+    fn into_py_err(self) -> PyErr;
+}
+
+impl IntoPyErr for laz::LasZipError {
+    fn into_py_err(self) -> PyErr {
+        use laz::LasZipError::*;
+        let message = self.to_string();
+        Python::attach(|py| {
+            let int = |value: u16| value.into_pyobject(py).unwrap().into_any();
+            match self {
+                UnknownLazItem(item_type) => {
+                    new_err::<UnknownLazItemError>(py, message, &[("item_type", int(item_type))])
+                }
+                UnsupportedLazItemVersion(item_type, version) => {
+                    new_err::<UnsupportedLazItemVersionError>(
+                        py,
+                        message,
+                        &[
+                            ("item_type", int(u16::from(item_type))),
+                            ("version", int(version)),
+                        ],
+                    )
+                }
+                UnknownCompressorType(compressor_type) => new_err::<UnknownCompressorTypeError>(
+                    py,
+                    message,
+                    &[("compressor_type", int(compressor_type))],
+                ),
+                UnsupportedCompressorType(compressor_type) => {
+                    new_err::<UnsupportedCompressorTypeError>(
+                        py,
+                        message,
+                        &[("compressor_type", int(compressor_type as u16))],
+                    )
+                }
+                UnsupportedPointFormat(point_format_id) => new_err::<UnsupportedPointFormatError>(
+                    py,
+                    message,
+                    &[("point_format_id", int(u16::from(point_format_id)))],
+                ),
+                MissingChunkTable => new_err::<MissingChunkTableError>(py, message, &[]),
+                IoError(error) => error.into_py_err(),
+                _ => PyErr::new::<LazrsError, _>(message),
+            }
+        })
+    }
+}
+
+impl IntoPyErr for std::io::Error {
+    fn into_py_err(self) -> PyErr {
+        // An exception raised by a method of a Python file object,
+        // it is re-raised as is
+        if self.get_ref().is_some_and(|error| error.is::<PyErr>()) {
+            return PyErr::from(self);
+        }
+        Python::attach(|py| {
+            let kind = format!("{:?}", self.kind());
+            new_err::<LazrsIoError>(
+                py,
+                format!("IoError: {}", self),
+                &[("kind", kind.into_pyobject(py).unwrap().into_any())],
+            )
+        })
+    }
+}
+
+pub(crate) fn into_py_err<E: IntoPyErr>(error: E) -> PyErr {
+    error.into_py_err()
+}
+
+/// Error for when a buffer does not have the length it should
+pub(crate) fn buffer_length_error(message: String, expected: usize, actual: usize) -> PyErr {
+    Python::attach(|py| {
+        new_err::<BufferLengthError>(
+            py,
+            message,
+            &[
+                ("expected", expected.into_pyobject(py).unwrap().into_any()),
+                ("actual", actual.into_pyobject(py).unwrap().into_any()),
+            ],
+        )
+    })
+}
+
+pub(crate) fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("LazrsError", py.get_type::<LazrsError>())?;
+    m.add("UnknownLazItemError", py.get_type::<UnknownLazItemError>())?;
+    m.add(
+        "UnsupportedLazItemVersionError",
+        py.get_type::<UnsupportedLazItemVersionError>(),
+    )?;
+    m.add(
+        "UnknownCompressorTypeError",
+        py.get_type::<UnknownCompressorTypeError>(),
+    )?;
+    m.add(
+        "UnsupportedCompressorTypeError",
+        py.get_type::<UnsupportedCompressorTypeError>(),
+    )?;
+    m.add(
+        "UnsupportedPointFormatError",
+        py.get_type::<UnsupportedPointFormatError>(),
+    )?;
+    m.add(
+        "MissingChunkTableError",
+        py.get_type::<MissingChunkTableError>(),
+    )?;
+    m.add("LazrsIoError", py.get_type::<LazrsIoError>())?;
+    m.add("BufferLengthError", py.get_type::<BufferLengthError>())?;
+    Ok(())
+}
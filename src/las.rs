@@ -0,0 +1,507 @@
+//! Minimal support of the LAS format: the public header block and the VLRs / EVLRs.
+//!
+//! This is just what we need to be able to locate and read (or write)
+//! the LAZ data of a file, the point records themselves are not interpreted.
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+/// Size of the header for LAS 1.0 to 1.2
+const HEADER_SIZE_1_0: u16 = 227;
+/// Size of the header for LAS 1.3 (adds the start of waveform data)
+const HEADER_SIZE_1_3: u16 = 235;
+/// Size of the header for LAS 1.4 (adds EVLRs and 64-bit point counts)
+const HEADER_SIZE_1_4: u16 = 375;
+
+const VLR_HEADER_SIZE: u64 = 54;
+const EVLR_HEADER_SIZE: u64 = 60;
+
+/// In LAZ files, the point format id has the bit 7 set
+/// (and sometimes bit 6 for old files).
+const COMPRESSION_BITS: u8 = 0b1100_0000;
+const COMPRESSION_BIT: u8 = 0b1000_0000;
+
+fn read_string<R: Read, const N: usize>(src: &mut R) -> std::io::Result<String> {
+    let mut bytes = [0u8; N];
+    src.read_exact(&mut bytes)?;
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(N);
+    Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+fn write_string<W: Write, const N: usize>(dst: &mut W, string: &str) -> std::io::Result<()> {
+    let mut bytes = [0u8; N];
+    let len = std::cmp::min(N, string.len());
+    bytes[..len].copy_from_slice(&string.as_bytes()[..len]);
+    dst.write_all(&bytes)
+}
+
+/// Size of the point record of the point format, without extra bytes
+pub(crate) fn base_point_size(point_format_id: u8) -> Option<u16> {
+    laz::LazItemRecordBuilder::default_for_point_format_id(point_format_id, 0)
+        .ok()
+        .map(|items| items.iter().map(laz::LazItem::size).sum())
+}
+
+pub(crate) fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+/// The public header block of a LAS file
+///
+/// Supports version 1.0 to 1.4
+#[pyclass(from_py_object)]
+#[derive(Clone, Debug)]
+pub(crate) struct LasHeader {
+    #[pyo3(get, set)]
+    pub(crate) file_source_id: u16,
+    #[pyo3(get, set)]
+    pub(crate) global_encoding: u16,
+    pub(crate) guid: [u8; 16],
+    #[pyo3(get, set)]
+    pub(crate) version_major: u8,
+    #[pyo3(get, set)]
+    pub(crate) version_minor: u8,
+    #[pyo3(get, set)]
+    pub(crate) system_identifier: String,
+    #[pyo3(get, set)]
+    pub(crate) generating_software: String,
+    #[pyo3(get, set)]
+    pub(crate) creation_day_of_year: u16,
+    #[pyo3(get, set)]
+    pub(crate) creation_year: u16,
+    #[pyo3(get)]
+    pub(crate) header_size: u16,
+    #[pyo3(get)]
+    pub(crate) offset_to_point_data: u32,
+    #[pyo3(get)]
+    pub(crate) number_of_vlrs: u32,
+    /// The point format id, without the bits marking compression
+    #[pyo3(get, set)]
+    pub(crate) point_format_id: u8,
+    #[pyo3(get, set)]
+    pub(crate) point_size: u16,
+    /// The number of points, the 64-bit value for LAS 1.4,
+    /// the legacy 32-bit value otherwise
+    #[pyo3(get, set)]
+    pub(crate) point_count: u64,
+    /// Number of points by return, 5 values are meaningful
+    /// for LAS < 1.4, 15 for LAS 1.4
+    #[pyo3(get, set)]
+    pub(crate) number_of_points_by_return: [u64; 15],
+    #[pyo3(get, set)]
+    pub(crate) scales: (f64, f64, f64),
+    #[pyo3(get, set)]
+    pub(crate) offsets: (f64, f64, f64),
+    #[pyo3(get, set)]
+    pub(crate) mins: (f64, f64, f64),
+    #[pyo3(get, set)]
+    pub(crate) maxs: (f64, f64, f64),
+    #[pyo3(get)]
+    pub(crate) start_of_waveform_data_packet_record: u64,
+    #[pyo3(get)]
+    pub(crate) start_of_first_evlr: u64,
+    #[pyo3(get)]
+    pub(crate) number_of_evlrs: u32,
+}
+
+impl LasHeader {
+    /// Reads the header, `src` must be at the start of the file.
+    pub(crate) fn read_from<R: Read>(src: &mut R) -> std::io::Result<Self> {
+        let mut signature = [0u8; 4];
+        src.read_exact(&mut signature)?;
+        if &signature != b"LASF" {
+            return Err(invalid_data("Invalid LAS file signature".to_string()));
+        }
+
+        let file_source_id = src.read_u16::<LittleEndian>()?;
+        let global_encoding = src.read_u16::<LittleEndian>()?;
+        let mut guid = [0u8; 16];
+        src.read_exact(&mut guid)?;
+        let version_major = src.read_u8()?;
+        let version_minor = src.read_u8()?;
+        if version_major != 1 || version_minor > 4 {
+            return Err(invalid_data(format!(
+                "Unsupported LAS version {}.{}",
+                version_major, version_minor
+            )));
+        }
+        let system_identifier = read_string::<_, 32>(src)?;
+        let generating_software = read_string::<_, 32>(src)?;
+        let creation_day_of_year = src.read_u16::<LittleEndian>()?;
+        let creation_year = src.read_u16::<LittleEndian>()?;
+        let header_size = src.read_u16::<LittleEndian>()?;
+        let offset_to_point_data = src.read_u32::<LittleEndian>()?;
+        let number_of_vlrs = src.read_u32::<LittleEndian>()?;
+        let point_format_id = src.read_u8()? & !COMPRESSION_BITS;
+        let point_size = src.read_u16::<LittleEndian>()?;
+        let legacy_point_count = src.read_u32::<LittleEndian>()?;
+        let mut number_of_points_by_return = [0u64; 15];
+        for value in &mut number_of_points_by_return[..5] {
+            *value = u64::from(src.read_u32::<LittleEndian>()?);
+        }
+        let mut read_triplet = || -> std::io::Result<(f64, f64, f64)> {
+            Ok((
+                src.read_f64::<LittleEndian>()?,
+                src.read_f64::<LittleEndian>()?,
+                src.read_f64::<LittleEndian>()?,
+            ))
+        };
+        let scales = read_triplet()?;
+        let offsets = read_triplet()?;
+        let (max_x, min_x) = (
+            src.read_f64::<LittleEndian>()?,
+            src.read_f64::<LittleEndian>()?,
+        );
+        let (max_y, min_y) = (
+            src.read_f64::<LittleEndian>()?,
+            src.read_f64::<LittleEndian>()?,
+        );
+        let (max_z, min_z) = (
+            src.read_f64::<LittleEndian>()?,
+            src.read_f64::<LittleEndian>()?,
+        );
+
+        let mut header = Self {
+            file_source_id,
+            global_encoding,
+            guid,
+            version_major,
+            version_minor,
+            system_identifier,
+            generating_software,
+            creation_day_of_year,
+            creation_year,
+            header_size,
+            offset_to_point_data,
+            number_of_vlrs,
+            point_format_id,
+            point_size,
+            point_count: u64::from(legacy_point_count),
+            number_of_points_by_return,
+            scales,
+            offsets,
+            mins: (min_x, min_y, min_z),
+            maxs: (max_x, max_y, max_z),
+            start_of_waveform_data_packet_record: 0,
+            start_of_first_evlr: 0,
+            number_of_evlrs: 0,
+        };
+
+        if version_minor >= 3 {
+            header.start_of_waveform_data_packet_record = src.read_u64::<LittleEndian>()?;
+        }
+
+        if version_minor >= 4 {
+            header.start_of_first_evlr = src.read_u64::<LittleEndian>()?;
+            header.number_of_evlrs = src.read_u32::<LittleEndian>()?;
+            let point_count = src.read_u64::<LittleEndian>()?;
+            let mut number_of_points_by_return = [0u64; 15];
+            for value in &mut number_of_points_by_return {
+                *value = src.read_u64::<LittleEndian>()?;
+            }
+            // Writers of point formats < 6 may only fill the legacy fields
+            if point_count != 0 || legacy_point_count == 0 {
+                header.point_count = point_count;
+                header.number_of_points_by_return = number_of_points_by_return;
+            }
+        }
+
+        // Skip any user-defined bytes that follow the header
+        let standard_size = header.standard_size();
+        if header_size > standard_size {
+            std::io::copy(
+                &mut src.take(u64::from(header_size - standard_size)),
+                &mut std::io::sink(),
+            )?;
+        }
+
+        Ok(header)
+    }
+
+    /// Writes the header, compressed (the point format id has its compression bit set).
+    ///
+    /// Only the standard part of the header is written, `header_size` should
+    /// thus be [Self::standard_size].
+    pub(crate) fn write_to<W: Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        dst.write_all(b"LASF")?;
+        dst.write_u16::<LittleEndian>(self.file_source_id)?;
+        dst.write_u16::<LittleEndian>(self.global_encoding)?;
+        dst.write_all(&self.guid)?;
+        dst.write_u8(self.version_major)?;
+        dst.write_u8(self.version_minor)?;
+        write_string::<_, 32>(dst, &self.system_identifier)?;
+        write_string::<_, 32>(dst, &self.generating_software)?;
+        dst.write_u16::<LittleEndian>(self.creation_day_of_year)?;
+        dst.write_u16::<LittleEndian>(self.creation_year)?;
+        dst.write_u16::<LittleEndian>(self.header_size)?;
+        dst.write_u32::<LittleEndian>(self.offset_to_point_data)?;
+        dst.write_u32::<LittleEndian>(self.number_of_vlrs)?;
+        dst.write_u8(self.point_format_id | COMPRESSION_BIT)?;
+        dst.write_u16::<LittleEndian>(self.point_size)?;
+
+        // Legacy fields must be 0 when the values can't be represented
+        let legacy_point_count = u32::try_from(self.point_count)
+            .ok()
+            .filter(|_| self.point_format_id < 6)
+            .unwrap_or(0);
+        dst.write_u32::<LittleEndian>(legacy_point_count)?;
+        for value in &self.number_of_points_by_return[..5] {
+            let legacy_value = if legacy_point_count == 0 {
+                0
+            } else {
+                u32::try_from(*value).unwrap_or(0)
+            };
+            dst.write_u32::<LittleEndian>(legacy_value)?;
+        }
+
+        for value in [self.scales, self.offsets] {
+            dst.write_f64::<LittleEndian>(value.0)?;
+            dst.write_f64::<LittleEndian>(value.1)?;
+            dst.write_f64::<LittleEndian>(value.2)?;
+        }
+        dst.write_f64::<LittleEndian>(self.maxs.0)?;
+        dst.write_f64::<LittleEndian>(self.mins.0)?;
+        dst.write_f64::<LittleEndian>(self.maxs.1)?;
+        dst.write_f64::<LittleEndian>(self.mins.1)?;
+        dst.write_f64::<LittleEndian>(self.maxs.2)?;
+        dst.write_f64::<LittleEndian>(self.mins.2)?;
+
+        if self.version_minor >= 3 {
+            dst.write_u64::<LittleEndian>(self.start_of_waveform_data_packet_record)?;
+        }
+
+        if self.version_minor >= 4 {
+            dst.write_u64::<LittleEndian>(self.start_of_first_evlr)?;
+            dst.write_u32::<LittleEndian>(self.number_of_evlrs)?;
+            dst.write_u64::<LittleEndian>(self.point_count)?;
+            for value in &self.number_of_points_by_return {
+                dst.write_u64::<LittleEndian>(*value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Size of the header as defined by the specification for the version
+    pub(crate) fn standard_size(&self) -> u16 {
+        match self.version_minor {
+            0..=2 => HEADER_SIZE_1_0,
+            3 => HEADER_SIZE_1_3,
+            _ => HEADER_SIZE_1_4,
+        }
+    }
+}
+
+#[pymethods]
+impl LasHeader {
+    /// Creates a new header for the point format.
+    ///
+    /// The `version_minor` defaults to 4 for point formats >= 6, 2 otherwise.
+    #[new]
+    #[pyo3(signature = (point_format_id, num_extra_bytes = 0, version_minor = None))]
+    fn new(point_format_id: u8, num_extra_bytes: u16, version_minor: Option<u8>) -> PyResult<Self> {
+        let point_size = base_point_size(point_format_id).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Point format {} is not supported",
+                point_format_id
+            ))
+        })? + num_extra_bytes;
+        let version_minor = version_minor.unwrap_or(if point_format_id >= 6 { 4 } else { 2 });
+
+        let mut header = Self {
+            file_source_id: 0,
+            global_encoding: 0,
+            guid: [0u8; 16],
+            version_major: 1,
+            version_minor,
+            system_identifier: String::new(),
+            generating_software: "lazrs".to_string(),
+            creation_day_of_year: 0,
+            creation_year: 0,
+            header_size: 0,
+            offset_to_point_data: 0,
+            number_of_vlrs: 0,
+            point_format_id,
+            point_size,
+            point_count: 0,
+            number_of_points_by_return: [0u64; 15],
+            scales: (0.01, 0.01, 0.01),
+            offsets: (0.0, 0.0, 0.0),
+            mins: (0.0, 0.0, 0.0),
+            maxs: (0.0, 0.0, 0.0),
+            start_of_waveform_data_packet_record: 0,
+            start_of_first_evlr: 0,
+            number_of_evlrs: 0,
+        };
+        header.header_size = header.standard_size();
+        header.offset_to_point_data = u32::from(header.header_size);
+        Ok(header)
+    }
+
+    #[getter]
+    fn guid<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.guid)
+    }
+
+    #[setter]
+    fn set_guid(&mut self, guid: [u8; 16]) {
+        self.guid = guid;
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<LasHeader(version: {}.{}, point format: {}, point count: {})>",
+            self.version_major, self.version_minor, self.point_format_id, self.point_count
+        )
+    }
+}
+
+/// A Variable Length Record, or an Extended Variable Length Record
+#[pyclass(from_py_object)]
+#[derive(Clone, Debug)]
+pub(crate) struct Vlr {
+    #[pyo3(get, set)]
+    pub(crate) user_id: String,
+    #[pyo3(get, set)]
+    pub(crate) record_id: u16,
+    #[pyo3(get, set)]
+    pub(crate) description: String,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Vlr {
+    /// Reads a VLR (`extended == false`) or an EVLR (`extended == true`)
+    pub(crate) fn read_from<R: Read>(src: &mut R, extended: bool) -> std::io::Result<Self> {
+        let _reserved = src.read_u16::<LittleEndian>()?;
+        let user_id = read_string::<_, 16>(src)?;
+        let record_id = src.read_u16::<LittleEndian>()?;
+        let record_length = if extended {
+            src.read_u64::<LittleEndian>()?
+        } else {
+            u64::from(src.read_u16::<LittleEndian>()?)
+        };
+        let description = read_string::<_, 32>(src)?;
+        let mut data = Vec::new();
+        src.take(record_length).read_to_end(&mut data)?;
+        if data.len() as u64 != record_length {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+
+        Ok(Self {
+            user_id,
+            record_id,
+            description,
+            data,
+        })
+    }
+
+    /// Writes a VLR (`extended == false`) or an EVLR (`extended == true`)
+    pub(crate) fn write_to<W: Write>(&self, dst: &mut W, extended: bool) -> std::io::Result<()> {
+        dst.write_u16::<LittleEndian>(0)?;
+        write_string::<_, 16>(dst, &self.user_id)?;
+        dst.write_u16::<LittleEndian>(self.record_id)?;
+        if extended {
+            dst.write_u64::<LittleEndian>(self.data.len() as u64)?;
+        } else {
+            let record_length = u16::try_from(self.data.len()).map_err(|_| {
+                invalid_data(format!(
+                    "VLR data is too big ({} bytes), it must be stored as an EVLR",
+                    self.data.len()
+                ))
+            })?;
+            dst.write_u16::<LittleEndian>(record_length)?;
+        }
+        write_string::<_, 32>(dst, &self.description)?;
+        dst.write_all(&self.data)
+    }
+
+    /// Number of bytes the VLR takes in a file
+    pub(crate) fn size_in_file(&self, extended: bool) -> u64 {
+        let header_size = if extended {
+            EVLR_HEADER_SIZE
+        } else {
+            VLR_HEADER_SIZE
+        };
+        header_size + self.data.len() as u64
+    }
+
+    pub(crate) fn is_laszip_vlr(&self) -> bool {
+        self.user_id == laz::LazVlr::USER_ID && self.record_id == laz::LazVlr::RECORD_ID
+    }
+}
+
+#[pymethods]
+impl Vlr {
+    #[new]
+    #[pyo3(signature = (user_id, record_id, data, description = String::new()))]
+    fn new(user_id: String, record_id: u16, data: Vec<u8>, description: String) -> Self {
+        Self {
+            user_id,
+            record_id,
+            description,
+            data,
+        }
+    }
+
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.data)
+    }
+
+    #[setter]
+    fn set_data(&mut self, data: Vec<u8>) {
+        self.data = data;
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Vlr(user_id: '{}', record_id: {}, data len: {})>",
+            self.user_id,
+            self.record_id,
+            self.data.len()
+        )
+    }
+}
+
+/// Everything that comes before the point data of a LAS file,
+/// plus the EVLRs which come after it.
+pub(crate) struct LasMetadata {
+    pub(crate) header: LasHeader,
+    pub(crate) vlrs: Vec<Vlr>,
+    pub(crate) evlrs: Vec<Vlr>,
+}
+
+impl LasMetadata {
+    /// Reads the header, VLRs and EVLRs
+    ///
+    /// `src` must be at the start of the file, when this function returns
+    /// it will be positioned at the start of the point data.
+    pub(crate) fn read_from<R: Read + Seek>(src: &mut R) -> std::io::Result<Self> {
+        let header = LasHeader::read_from(src)?;
+
+        let mut vlrs = Vec::with_capacity(header.number_of_vlrs as usize);
+        for _ in 0..header.number_of_vlrs {
+            vlrs.push(Vlr::read_from(src, false)?);
+        }
+
+        let mut evlrs = Vec::with_capacity(header.number_of_evlrs as usize);
+        if header.number_of_evlrs > 0 {
+            src.seek(SeekFrom::Start(header.start_of_first_evlr))?;
+            for _ in 0..header.number_of_evlrs {
+                evlrs.push(Vlr::read_from(src, true)?);
+            }
+        }
+
+        src.seek(SeekFrom::Start(u64::from(header.offset_to_point_data)))?;
+        Ok(Self {
+            header,
+            vlrs,
+            evlrs,
+        })
+    }
+
+    pub(crate) fn laszip_vlr(&self) -> Option<&Vlr> {
+        self.vlrs.iter().find(|vlr| vlr.is_laszip_vlr())
+    }
+}
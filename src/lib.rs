@@ -1,25 +1,84 @@
-use std::io::{BufReader, BufWriter, Read, Write};
-
-use adapters::{BufReadWritePyFileObject, PyFileObject};
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+
+use adapters::{PyFileObject, ReadWriter, Reader, Writer};
+use chunk_iter::ChunkIterator;
+use chunk_table::{chunk_table_from_py, ChunkTable};
+use errors::{buffer_length_error, into_py_err, LazrsError};
+use progress::Progress;
+use pyo3::buffer::PyBuffer;
 use pyo3::prelude::*;
-use pyo3::types::{PyAny, PyBytes, PyList, PyType};
-use pyo3::{create_exception, wrap_pyfunction};
+use pyo3::types::{PyAny, PyBytes, PyDict, PyList, PyType};
+use pyo3::wrap_pyfunction;
+use rollback::Rollback;
+use selective::{ReadStats, SelectiveReader};
+use threads::{Pool, ThreadPool};
+use vlr::VlrHeader;
 
 mod adapters;
+mod append;
+mod arrays;
+mod chunk_iter;
+mod chunk_table;
+mod columns;
+mod convert;
+mod errors;
+mod las;
+mod partial;
+mod progress;
+mod reader;
+mod recovery;
+mod rollback;
+mod selective;
+mod threads;
+mod transcode;
+mod validate;
+mod vlr;
+mod writer;
+
+/// The bytes of a Python object that supports the buffer protocol.
+///
+/// The buffer stays exported while this is alive, so the object cannot be resized
+/// or freed while the bytes are used, even when the GIL is released.
+pub(crate) struct Bytes(PyBuffer<u8>);
+
+impl std::ops::Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.0.buf_ptr() as *const u8, self.0.len_bytes()) }
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+/// The bytes of a writable Python object that supports the buffer protocol,
+/// see `Bytes`
+pub(crate) struct BytesMut(PyBuffer<u8>);
 
-create_exception!(lazrs, LazrsError, pyo3::exceptions::PyRuntimeError);
+impl std::ops::Deref for BytesMut {
+    type Target = [u8];
 
-fn as_bytes<'py>(object: &Bound<'py, PyAny>) -> PyResult<&'py [u8]> {
-    let buffer = pyo3::buffer::PyBuffer::<u8>::get(object)?;
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.0.buf_ptr() as *const u8, self.0.len_bytes()) }
+    }
+}
 
-    let slc =
-        unsafe { std::slice::from_raw_parts(buffer.buf_ptr() as *const u8, buffer.len_bytes()) };
+impl std::ops::DerefMut for BytesMut {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.0.buf_ptr() as *mut u8, self.0.len_bytes()) }
+    }
+}
 
-    Ok(slc)
+fn as_bytes(object: &Bound<'_, PyAny>) -> PyResult<Bytes> {
+    PyBuffer::<u8>::get(object).map(Bytes)
 }
 
-fn as_mut_bytes<'py>(object: &Bound<'py, PyAny>) -> PyResult<&'py mut [u8]> {
-    let buffer = pyo3::buffer::PyBuffer::<u8>::get(object)?;
+fn as_mut_bytes(object: &Bound<'_, PyAny>) -> PyResult<BytesMut> {
+    let buffer = PyBuffer::<u8>::get(object)?;
 
     if buffer.readonly() {
         return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
@@ -27,26 +86,143 @@ fn as_mut_bytes<'py>(object: &Bound<'py, PyAny>) -> PyResult<&'py mut [u8]> {
         ));
     }
 
-    let slc =
-        unsafe { std::slice::from_raw_parts_mut(buffer.buf_ptr() as *mut u8, buffer.len_bytes()) };
-
-    Ok(slc)
+    Ok(BytesMut(buffer))
 }
 
-fn into_py_err<T: std::fmt::Display>(error: T) -> PyErr {
-    PyErr::new::<LazrsError, _>(format!("{}", error))
-}
-
-#[pyclass(from_py_object)]
+/// The fields that can be selected, and their bit in the selection,
+/// the `xy_returns_channel` are always decompressed
+const SELECTION_FIELDS: [(&str, u32); 13] = [
+    (
+        "xy_returns_channel",
+        laz::DecompressionSelection::XY_RETURNS_CHANNEL,
+    ),
+    ("z", laz::DecompressionSelection::Z),
+    (
+        "classification",
+        laz::DecompressionSelection::CLASSIFICATION,
+    ),
+    ("flags", laz::DecompressionSelection::FLAGS),
+    ("intensity", laz::DecompressionSelection::INTENSITY),
+    ("scan_angle", laz::DecompressionSelection::SCAN_ANGLE),
+    ("user_data", laz::DecompressionSelection::USER_DATA),
+    (
+        "point_source_id",
+        laz::DecompressionSelection::POINT_SOURCE_ID,
+    ),
+    ("gps_time", laz::DecompressionSelection::GPS_TIME),
+    ("rgb", laz::DecompressionSelection::RGB),
+    ("nir", laz::DecompressionSelection::NIR),
+    ("wavepacket", laz::DecompressionSelection::WAVEPACKET),
+    ("extra_bytes", laz::DecompressionSelection::ALL_EXTRA_BYTES),
+];
+
+/// Which fields are decompressed, only the point formats 6 to 10 support
+/// skipping the decompression of fields.
+///
+/// Selections can be combined with `|`, `&` and `~`.
+#[pyclass(frozen, from_py_object)]
 #[derive(Copy, Clone, Debug)]
 struct DecompressionSelection(laz::DecompressionSelection);
 
+impl DecompressionSelection {
+    fn field_bit(field: &str) -> PyResult<u32> {
+        SELECTION_FIELDS
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, bit)| *bit)
+            .ok_or_else(|| {
+                let names = SELECTION_FIELDS.map(|(name, _)| name).join(", ");
+                PyErr::new::<LazrsError, _>(format!(
+                    "Unknown field '{}', the fields are: {}",
+                    field, names
+                ))
+            })
+    }
+
+    /// The bits of the known fields, the others do not change what is decompressed
+    fn bits(&self) -> u32 {
+        let known = SELECTION_FIELDS.iter().fold(0, |bits, (_, bit)| bits | bit);
+        self.0 .0 & known
+    }
+}
+
 #[pymethods]
 impl DecompressionSelection {
     #[new]
     fn new(value: u32) -> Self {
         Self(laz::DecompressionSelection(value))
     }
+
+    /// Selects the fields named in `fields`, see `fields` for the names
+    #[staticmethod]
+    fn from_fields(fields: Vec<String>) -> PyResult<Self> {
+        let value = fields.iter().try_fold(0, |value, field| {
+            Self::field_bit(field).map(|bit| value | bit)
+        })?;
+        Ok(Self::new(value))
+    }
+
+    /// Selects all the fields
+    #[staticmethod]
+    fn all() -> Self {
+        Self(laz::DecompressionSelection::all())
+    }
+
+    /// Selects only the fields that are always decompressed:
+    /// x, y, the return number, the number of returns and the scanner channel
+    #[staticmethod]
+    fn xy_returns_channel() -> Self {
+        Self(laz::DecompressionSelection::xy_returns_channel())
+    }
+
+    #[getter]
+    fn value(&self) -> u32 {
+        self.0 .0
+    }
+
+    /// The names of the selected fields: `"xy_returns_channel"`, `"z"`, `"classification"`,
+    /// `"flags"`, `"intensity"`, `"scan_angle"`, `"user_data"`, `"point_source_id"`,
+    /// `"gps_time"`, `"rgb"`, `"nir"`, `"wavepacket"` and `"extra_bytes"`
+    #[getter]
+    fn fields(&self) -> Vec<&'static str> {
+        SELECTION_FIELDS
+            .iter()
+            .filter(|(_, bit)| self.bits() & bit == *bit)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    fn __contains__(&self, field: &str) -> PyResult<bool> {
+        let bit = Self::field_bit(field)?;
+        Ok(self.bits() & bit == bit)
+    }
+
+    fn __or__(&self, other: &Self) -> Self {
+        Self::new(self.0 .0 | other.0 .0)
+    }
+
+    fn __and__(&self, other: &Self) -> Self {
+        Self::new(self.0 .0 & other.0 .0)
+    }
+
+    fn __invert__(&self) -> Self {
+        Self::new(!self.0 .0)
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+
+    fn __hash__(&self) -> u64 {
+        u64::from(self.bits())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<DecompressionSelection(fields: [{}])>",
+            self.fields().join(", ")
+        )
+    }
 }
 
 #[pyclass]
@@ -54,33 +230,90 @@ struct LazVlr {
     vlr: laz::LazVlr,
 }
 
+impl LazVlr {
+    fn build(
+        mut builder: laz::LazVlrBuilder,
+        use_variable_size_chunks: bool,
+        chunk_size: Option<u32>,
+    ) -> PyResult<Self> {
+        match (use_variable_size_chunks, chunk_size) {
+            (true, Some(_)) => {
+                return Err(PyErr::new::<LazrsError, _>(
+                    "A chunk size cannot be given for variable-size chunks",
+                ))
+            }
+            (false, Some(0)) => {
+                return Err(PyErr::new::<LazrsError, _>("The chunk size cannot be 0"))
+            }
+            (false, Some(chunk_size)) => builder = builder.with_fixed_chunk_size(chunk_size),
+            (true, None) => builder = builder.with_variable_chunk_size(),
+            (false, None) => {}
+        }
+        Ok(LazVlr {
+            vlr: builder.build(),
+        })
+    }
+
+    fn header(&self) -> PyResult<VlrHeader> {
+        Ok(VlrHeader::of(&self.vlr)?)
+    }
+}
+
 #[pymethods]
 impl LazVlr {
     #[new]
     fn new<'py>(record_data: &Bound<'py, PyAny>) -> PyResult<Self> {
         let vlr_data = as_bytes(record_data)?;
-        let vlr = laz::LazVlr::read_from(vlr_data).map_err(into_py_err)?;
+        let vlr = laz::LazVlr::read_from(&*vlr_data).map_err(into_py_err)?;
         Ok(LazVlr { vlr })
     }
 
+    /// `chunk_size` is the number of points of the chunks,
+    /// when they are not of variable size.
     #[classmethod]
-    #[pyo3(signature = (point_format_id, num_extra_bytes, use_variable_size_chunks=false))]
+    #[pyo3(signature = (point_format_id, num_extra_bytes, use_variable_size_chunks=false, chunk_size=None))]
     fn new_for_compression<'py>(
         _cls: &Bound<'py, PyType>,
         point_format_id: u8,
         num_extra_bytes: u16,
         use_variable_size_chunks: bool,
+        chunk_size: Option<u32>,
     ) -> PyResult<Self> {
-        let mut builder = laz::LazVlrBuilder::default()
+        let builder = laz::LazVlrBuilder::default()
             .with_point_format(point_format_id, num_extra_bytes)
             .map_err(into_py_err)?;
+        Self::build(builder, use_variable_size_chunks, chunk_size)
+    }
 
-        if use_variable_size_chunks {
-            builder = builder.with_variable_chunk_size();
+    /// Creates a VLR from a list of `LazItem`, in the order of the point record.
+    ///
+    /// The versions of the items must be supported by laz,
+    /// 1 or 2 for the items of point formats < 6, 3 for the others.
+    #[classmethod]
+    #[pyo3(signature = (items, use_variable_size_chunks=false, chunk_size=None))]
+    fn from_items<'py>(
+        _cls: &Bound<'py, PyType>,
+        items: Vec<PyRef<'py, vlr::LazItem>>,
+        use_variable_size_chunks: bool,
+        chunk_size: Option<u32>,
+    ) -> PyResult<Self> {
+        if items.is_empty() {
+            return Err(PyErr::new::<LazrsError, _>(
+                "The VLR needs at least one item",
+            ));
         }
-
-        let vlr = builder.build();
-        Ok(LazVlr { vlr })
+        if let Some(item) = items.iter().find(|item| !(1..=4).contains(&item.version)) {
+            return Err(PyErr::new::<LazrsError, _>(format!(
+                "Version {} of the items is not supported",
+                item.version
+            )));
+        }
+        let builder = laz::LazVlrBuilder::new(vlr::laz_items(&items)?);
+        let this = Self::build(builder, use_variable_size_chunks, chunk_size)?;
+        // Creating a compressor checks that the items and their versions are supported
+        laz::LasZipCompressor::new(std::io::Cursor::new(Vec::new()), this.vlr.clone())
+            .map_err(into_py_err)?;
+        Ok(this)
     }
 
     fn uses_variable_size_chunks(&self) -> bool {
@@ -95,11 +328,65 @@ impl LazVlr {
         self.vlr.items_size()
     }
 
+    /// Returns the name of the compressor type
+    /// (`"PointWise"`, `"PointWiseChunked"` or `"LayeredChunked"`)
+    fn compressor(&self) -> PyResult<&'static str> {
+        Ok(vlr::compressor_name(self.header()?.compressor))
+    }
+
+    fn coder(&self) -> PyResult<u16> {
+        Ok(self.header()?.coder)
+    }
+
+    /// Returns the (major, minor, revision) version of LASzip that wrote the VLR
+    fn version(&self) -> PyResult<(u8, u8, u16)> {
+        let header = self.header()?;
+        Ok((
+            header.version_major,
+            header.version_minor,
+            header.version_revision,
+        ))
+    }
+
+    fn options(&self) -> PyResult<u32> {
+        Ok(self.header()?.options)
+    }
+
+    /// Returns the number of special EVLRs, -1 if unused
+    fn number_of_special_evlrs(&self) -> PyResult<i64> {
+        Ok(self.header()?.number_of_special_evlrs)
+    }
+
+    /// Returns the offset to the special EVLRs, -1 if unused
+    fn offset_to_special_evlrs(&self) -> PyResult<i64> {
+        Ok(self.header()?.offset_to_special_evlrs)
+    }
+
+    /// Returns the items, in the order of the point record
+    fn items(&self) -> Vec<vlr::LazItem> {
+        self.vlr.items().iter().map(vlr::LazItem::from).collect()
+    }
+
+    /// Returns the numpy structured dtype of the points
+    fn dtype<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        arrays::points_dtype(py, &self.vlr)
+    }
+
+    /// Returns the (name, numpy format) of the fields decompressed with the selection,
+    /// which are the fields that can be decompressed in their own buffer
+    #[pyo3(signature = (selection = None))]
+    fn fields(&self, selection: Option<DecompressionSelection>) -> Vec<(&'static str, String)> {
+        let selection = selection.map_or_else(laz::DecompressionSelection::all, |s| s.0);
+        arrays::point_fields(&self.vlr)
+            .into_iter()
+            .filter(|field| field.is_selected(selection))
+            .map(|field| (field.name, field.format))
+            .collect()
+    }
+
     fn record_data(&self) -> PyResult<Py<PyAny>> {
         let mut data = std::io::Cursor::new(Vec::<u8>::new());
-        self.vlr
-            .write_to(&mut data)
-            .map_err(|e| PyErr::new::<LazrsError, String>(format!("{}", e)))?;
+        self.vlr.write_to(&mut data).map_err(into_py_err)?;
 
         Python::attach(|py| {
             let bytes = PyBytes::new(py, data.get_ref()).into_any().unbind();
@@ -110,106 +397,324 @@ impl LazVlr {
 
 #[pyclass]
 struct ParLasZipCompressor {
-    compressor: laz::ParLasZipCompressor<BufWriter<PyFileObject>>,
+    /// `None` once `done()` was called
+    compressor: Option<laz::ParLasZipCompressor<Writer>>,
+    rollback: Rollback,
+    pool: Pool,
+}
+
+impl ParLasZipCompressor {
+    fn compressor(&mut self) -> PyResult<&mut laz::ParLasZipCompressor<Writer>> {
+        self.compressor
+            .as_mut()
+            .ok_or_else(|| PyErr::new::<LazrsError, _>("The compressor is closed"))
+    }
+
+    /// Closes the compressor, and removes what it wrote from the destination
+    fn abort(&mut self) -> PyResult<()> {
+        let Some(compressor) = self.compressor.take() else {
+            return Ok(());
+        };
+        self.rollback
+            .apply(&mut compressor.into_inner())
+            .map_err(into_py_err)
+    }
 }
 
 #[pymethods]
 impl ParLasZipCompressor {
     #[new]
-    fn new(dest: Py<PyAny>, vlr: &LazVlr) -> PyResult<Self> {
-        let dest = Python::attach(|py| PyFileObject::new(py, dest))?;
-        let dest = BufWriter::new(dest);
+    #[pyo3(signature = (dest, vlr, num_threads = None, thread_pool = None))]
+    fn new(
+        dest: Py<PyAny>,
+        vlr: &LazVlr,
+        num_threads: Option<usize>,
+        thread_pool: Option<PyRef<ThreadPool>>,
+    ) -> PyResult<Self> {
+        let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
+        let mut dest = Python::attach(|py| Writer::new(py, dest))?;
+        let rollback = Rollback::compressor(&mut dest)?;
         let compressor =
             laz::ParLasZipCompressor::new(dest, vlr.vlr.clone()).map_err(into_py_err)?;
-        Ok(ParLasZipCompressor { compressor })
+        Ok(ParLasZipCompressor {
+            compressor: Some(compressor),
+            rollback,
+            pool,
+        })
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Calls `done()`, or removes what was written if an exception was raised
+    fn __exit__(
+        &mut self,
+        py: Python,
+        exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        if self.compressor.is_some() {
+            match exc_type {
+                None => {
+                    if let Err(error) = self.done(py) {
+                        return self.abort().and(Err(error));
+                    }
+                }
+                Some(_) => self.abort()?,
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether `done()` was called
+    #[getter]
+    fn closed(&self) -> bool {
+        self.compressor.is_none()
     }
 
     pub fn reserve_offset_to_chunk_table(&mut self) -> PyResult<()> {
-        self.compressor
+        let compressor = self.compressor()?;
+        compressor
             .reserve_offset_to_chunk_table()
             .map_err(into_py_err)?;
-        self.compressor.get_mut().flush().map_err(into_py_err)
+        compressor.get_mut().flush().map_err(into_py_err)
     }
 
-    fn compress_many<'py>(&mut self, points: &Bound<'py, PyAny>) -> PyResult<()> {
+    /// Compresses the points.
+    ///
+    /// `progress` is called with `(points_done, total)` after each batch of chunks.
+    #[pyo3(signature = (points, progress = None))]
+    fn compress_many<'py>(
+        &mut self,
+        py: Python<'py>,
+        points: &Bound<'py, PyAny>,
+        progress: Option<Py<PyAny>>,
+    ) -> PyResult<()> {
         let point_bytes = as_bytes(points)?;
 
-        self.compressor
-            .compress_many(point_bytes)
-            .map_err(into_py_err)
+        let pool = self.pool.clone();
+        let compressor = self.compressor()?;
+        let vlr = compressor.vlr();
+        let batch_size = progress::batch_size(vlr, pool.install(rayon::current_num_threads));
+        let mut progress =
+            Progress::new(progress, vlr, point_bytes.len() as u64 / vlr.items_size());
+        py.detach(|| {
+            progress.run(point_bytes.chunks(batch_size), |batch| {
+                pool.install(|| compressor.compress_many(batch))
+            })
+        })
     }
 
-    pub fn compress_chunks<'py>(&mut self, chunks: &Bound<'py, PyList>) -> PyResult<()> {
+    pub fn compress_chunks<'py>(
+        &mut self,
+        py: Python<'py>,
+        chunks: &Bound<'py, PyList>,
+    ) -> PyResult<()> {
         let chunks = chunks
             .iter()
             .map(|chunk| as_bytes(&chunk))
-            .collect::<PyResult<Vec<&[u8]>>>()?;
-        self.compressor.compress_chunks(chunks)?;
+            .collect::<PyResult<Vec<Bytes>>>()?;
+        let pool = self.pool.clone();
+        let compressor = self.compressor()?;
+        py.detach(|| pool.install(|| compressor.compress_chunks(chunks)))?;
         Ok(())
     }
 
-    fn done(&mut self) -> PyResult<()> {
-        self.compressor.done().map_err(into_py_err)?;
-        self.compressor.get_mut().flush().map_err(into_py_err)
+    /// Finishes the compression, the compressor can no longer be used afterwards
+    fn done(&mut self, py: Python) -> PyResult<()> {
+        let pool = self.pool.clone();
+        let compressor = self.compressor()?;
+        py.detach(|| pool.install(|| compressor.done()))
+            .map_err(into_py_err)?;
+        compressor.get_mut().flush().map_err(into_py_err)?;
+        self.compressor = None;
+        Ok(())
     }
 }
 
 #[pyclass]
 struct ParLasZipDecompressor {
-    decompressor: laz::ParLasZipDecompressor<BufReader<PyFileObject>>,
+    decompressor: laz::ParLasZipDecompressor<Reader>,
+    vlr: laz::LazVlr,
+    selection: laz::DecompressionSelection,
+    ranges: partial::RangeDecompressor,
+    pool: Pool,
 }
 
 #[pymethods]
 impl ParLasZipDecompressor {
     #[new]
-    #[pyo3(signature=(source, vlr_record_data, selection = None))]
+    #[pyo3(signature=(source, vlr_record_data, selection = None, num_threads = None, thread_pool = None))]
     fn new<'py>(
         source: Py<PyAny>,
         vlr_record_data: &Bound<'py, PyAny>,
         selection: Option<DecompressionSelection>,
+        num_threads: Option<usize>,
+        thread_pool: Option<PyRef<'py, ThreadPool>>,
     ) -> PyResult<Self> {
+        let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
         Python::attach(|py| {
-            let source = BufReader::new(PyFileObject::new(py, source)?);
-            let vlr = laz::LazVlr::read_from(as_bytes(vlr_record_data)?).map_err(into_py_err)?;
+            let mut source = Reader::new(py, source)?;
+            let ranges = partial::RangeDecompressor::new(source.stream_position()?, pool.clone());
+            let vlr = laz::LazVlr::read_from(&*as_bytes(vlr_record_data)?).map_err(into_py_err)?;
 
             if let Some(selection) = selection {
+                let source = SelectiveReader::wrap(source, &vlr, selection.0)?;
                 Ok(ParLasZipDecompressor {
-                    decompressor: laz::ParLasZipDecompressor::selective(source, vlr, selection.0)
-                        .map_err(into_py_err)?,
+                    decompressor: laz::ParLasZipDecompressor::selective(
+                        source,
+                        vlr.clone(),
+                        selection.0,
+                    )
+                    .map_err(into_py_err)?,
+                    vlr,
+                    selection: selection.0,
+                    ranges,
+                    pool,
                 })
             } else {
                 Ok(ParLasZipDecompressor {
-                    decompressor: laz::ParLasZipDecompressor::new(source, vlr)
+                    decompressor: laz::ParLasZipDecompressor::new(source, vlr.clone())
                         .map_err(into_py_err)?,
+                    vlr,
+                    selection: laz::DecompressionSelection::all(),
+                    ranges,
+                    pool,
                 })
             }
         })
     }
 
-    fn decompress_many<'py>(&mut self, points: &Bound<'py, PyAny>) -> PyResult<()> {
-        let points = as_mut_bytes(points)?;
-        self.decompressor
-            .decompress_many(points)
-            .map_err(into_py_err)?;
-        Ok(())
+    /// Decompresses points into the `points` buffer.
+    ///
+    /// `points` can also be a dict mapping field names to buffers,
+    /// in which case each field is decompressed in its own buffer.
+    ///
+    /// `progress` is called with `(points_done, total)` after each batch of chunks.
+    #[pyo3(signature = (points, progress = None))]
+    fn decompress_many<'py>(
+        &mut self,
+        py: Python<'py>,
+        points: &Bound<'py, PyAny>,
+        progress: Option<Py<PyAny>>,
+    ) -> PyResult<()> {
+        let decompressor = &mut self.decompressor;
+        if let Ok(columns) = points.cast::<PyDict>() {
+            let mut columns = columns::Columns::from_dict(&self.vlr, self.selection, columns)?;
+            let mut progress = Progress::new(progress, &self.vlr, columns.num_points() as u64);
+            return py.detach(|| {
+                columns.decompress_with(|points| {
+                    self.pool
+                        .install(|| decompressor.decompress_many(points))
+                        .map_err(into_py_err)?;
+                    progress.advance(points.len())
+                })
+            });
+        }
+        let mut points = as_mut_bytes(points)?;
+        let batch_size =
+            progress::batch_size(&self.vlr, self.pool.install(rayon::current_num_threads));
+        let mut progress = Progress::new(
+            progress,
+            &self.vlr,
+            points.len() as u64 / self.vlr.items_size(),
+        );
+        py.detach(|| {
+            progress.run(points.chunks_mut(batch_size), |batch| {
+                self.pool.install(|| decompressor.decompress_many(batch))
+            })
+        })
+    }
+
+    /// Decompresses the next `n` points into a new numpy structured array
+    fn read_points<'py>(&mut self, py: Python<'py>, n: usize) -> PyResult<Bound<'py, PyAny>> {
+        let decompressor = &mut self.decompressor;
+        arrays::new_points_array(py, &self.vlr, n, |points| {
+            py.detach(|| self.pool.install(|| decompressor.decompress_many(points)))
+                .map_err(into_py_err)
+        })
+    }
+
+    /// Decompresses the `count` points starting at `start` into `out`.
+    ///
+    /// Only the chunks that contain the points are decompressed, in parallel.
+    /// Afterwards, the decompressor is positioned after the last point of the range.
+    ///
+    /// `point_count` is the number of points of the chunks (the point count of the
+    /// LAS header), it must be given with fixed size chunks for the point formats < 6,
+    /// which do not store the number of points of the last chunk.
+    #[pyo3(signature = (start, count, out, point_count = None))]
+    fn decompress_range<'py>(
+        &mut self,
+        py: Python<'py>,
+        start: u64,
+        count: u64,
+        out: &Bound<'py, PyAny>,
+        point_count: Option<u64>,
+    ) -> PyResult<()> {
+        self.ranges.decompress_range(
+            py,
+            self.decompressor.get_mut(),
+            &self.vlr,
+            start,
+            count,
+            &mut as_mut_bytes(out)?,
+            self.selection,
+            point_count,
+        )?;
+        self.decompressor.seek(start + count).map_err(into_py_err)
+    }
+
+    /// Returns an iterator over the chunks, which yields the points of each chunk.
+    ///
+    /// The next `prefetch` chunks (by default, as many as there are threads)
+    /// are decompressed in parallel, in the background.
+    ///
+    /// With fixed size chunks, the number of points of the last chunk is only stored
+    /// for the point formats >= 6, for the other formats `point_count`
+    /// (the point count of the LAS header) must be given.
+    ///
+    /// The decompressor must not be used while iterating,
+    /// it is positioned after the last point once the iteration is done.
+    #[pyo3(signature = (point_count = None, prefetch = None))]
+    fn iter_chunks(
+        slf: &Bound<'_, Self>,
+        point_count: Option<u64>,
+        prefetch: Option<usize>,
+    ) -> PyResult<ChunkIterator> {
+        ChunkIterator::parallel(slf, point_count, prefetch)
     }
 
     pub fn seek(&mut self, point_idx: u64) -> PyResult<()> {
         self.decompressor.seek(point_idx).map_err(into_py_err)
     }
 
+    /// Reads the bytes at the position of the source as they are,
+    /// the layers that are not selected included.
     pub fn read_raw_bytes_into<'py>(&mut self, bytes: &Bound<'py, PyAny>) -> PyResult<()> {
-        let slc = as_mut_bytes(bytes)?;
+        let mut slc = as_mut_bytes(bytes)?;
         self.decompressor
             .get_mut()
-            .read_exact(slc)
+            .read_exact_raw(&mut slc)
             .map_err(into_py_err)
     }
+
+    /// The bytes read from the source, `None` when no layers are skipped.
+    ///
+    /// Layers are only skipped for the point formats 6 to 10,
+    /// when the decompressor is created with a selection and the source has a chunk table.
+    fn read_stats(&self) -> Option<ReadStats> {
+        selective::read_stats(self.decompressor.get())
+    }
 }
 
 #[pyclass]
 struct LasZipDecompressor {
-    decompressor: laz::LasZipDecompressor<'static, BufReader<PyFileObject>>,
+    decompressor: laz::LasZipDecompressor<'static, Reader>,
+    selection: laz::DecompressionSelection,
+    ranges: partial::RangeDecompressor,
 }
 
 #[pymethods]
@@ -222,27 +727,112 @@ impl LasZipDecompressor {
         selection: Option<DecompressionSelection>,
     ) -> PyResult<Self> {
         Python::attach(|py| {
-            let source = BufReader::new(PyFileObject::new(py, source)?);
-            let vlr = laz::LazVlr::read_from(as_bytes(record_data)?).map_err(into_py_err)?;
+            let mut source = Reader::new(py, source)?;
+            let ranges =
+                partial::RangeDecompressor::new(source.stream_position()?, Pool::default());
+            let vlr = laz::LazVlr::read_from(&*as_bytes(record_data)?).map_err(into_py_err)?;
 
             if let Some(selection) = selection {
+                let source = SelectiveReader::wrap(source, &vlr, selection.0)?;
                 Ok(Self {
                     decompressor: laz::LasZipDecompressor::selective(source, vlr, selection.0)
                         .map_err(into_py_err)?,
+                    selection: selection.0,
+                    ranges,
                 })
             } else {
                 Ok(Self {
                     decompressor: laz::LasZipDecompressor::new(source, vlr).map_err(into_py_err)?,
+                    selection: laz::DecompressionSelection::all(),
+                    ranges,
                 })
             }
         })
     }
 
-    pub fn decompress_many<'py>(&mut self, dest: &Bound<'py, PyAny>) -> PyResult<()> {
-        let slc = as_mut_bytes(dest)?;
-        self.decompressor
-            .decompress_many(slc)
-            .map_err(|e| PyErr::new::<LazrsError, String>(format!("{}", e)))
+    /// Decompresses points into the `dest` buffer.
+    ///
+    /// `dest` can also be a dict mapping field names to buffers,
+    /// in which case each field is decompressed in its own buffer.
+    ///
+    /// `progress` is called with `(points_done, total)` after each chunk.
+    #[pyo3(signature = (dest, progress = None))]
+    pub fn decompress_many<'py>(
+        &mut self,
+        py: Python<'py>,
+        dest: &Bound<'py, PyAny>,
+        progress: Option<Py<PyAny>>,
+    ) -> PyResult<()> {
+        let vlr = self.decompressor.vlr().clone();
+        if let Ok(columns) = dest.cast::<PyDict>() {
+            let mut columns = columns::Columns::from_dict(&vlr, self.selection, columns)?;
+            let mut progress = Progress::new(progress, &vlr, columns.num_points() as u64);
+            return py.detach(|| {
+                columns.decompress_with(|points| {
+                    self.decompressor
+                        .decompress_many(points)
+                        .map_err(into_py_err)?;
+                    progress.advance(points.len())
+                })
+            });
+        }
+        let mut slc = as_mut_bytes(dest)?;
+        let mut progress = Progress::new(progress, &vlr, slc.len() as u64 / vlr.items_size());
+        py.detach(|| {
+            progress.run(slc.chunks_mut(progress::batch_size(&vlr, 1)), |batch| {
+                self.decompressor.decompress_many(batch)
+            })
+        })
+    }
+
+    /// Decompresses the next `n` points into a new numpy structured array
+    pub fn read_points<'py>(&mut self, py: Python<'py>, n: usize) -> PyResult<Bound<'py, PyAny>> {
+        let decompressor = &mut self.decompressor;
+        arrays::new_points_array(py, &decompressor.vlr().clone(), n, |points| {
+            py.detach(|| decompressor.decompress_many(points))
+                .map_err(into_py_err)
+        })
+    }
+
+    /// Decompresses the `count` points starting at `start` into `out`.
+    ///
+    /// Only the chunks that contain the points are decompressed, in parallel.
+    /// Afterwards, the decompressor is positioned after the last point of the range.
+    ///
+    /// `point_count` is the number of points of the chunks (the point count of the
+    /// LAS header), it must be given with fixed size chunks for the point formats < 6,
+    /// which do not store the number of points of the last chunk.
+    #[pyo3(signature = (start, count, out, point_count = None))]
+    pub fn decompress_range<'py>(
+        &mut self,
+        py: Python<'py>,
+        start: u64,
+        count: u64,
+        out: &Bound<'py, PyAny>,
+        point_count: Option<u64>,
+    ) -> PyResult<()> {
+        let vlr = self.decompressor.vlr().clone();
+        self.ranges.decompress_range(
+            py,
+            self.decompressor.get_mut(),
+            &vlr,
+            start,
+            count,
+            &mut as_mut_bytes(out)?,
+            self.selection,
+            point_count,
+        )?;
+        self.decompressor.seek(start + count).map_err(into_py_err)
+    }
+
+    /// Returns an iterator over the chunks, which yields the points of each chunk.
+    ///
+    /// With fixed size chunks, the number of points of the last chunk is only stored
+    /// for the point formats >= 6, for the other formats `point_count`
+    /// (the point count of the LAS header) must be given.
+    #[pyo3(signature = (point_count = None))]
+    pub fn iter_chunks(slf: &Bound<'_, Self>, point_count: Option<u64>) -> PyResult<ChunkIterator> {
+        ChunkIterator::sequential(slf, point_count)
     }
 
     pub fn seek(&mut self, point_idx: u64) -> PyResult<()> {
@@ -257,99 +847,205 @@ impl LasZipDecompressor {
 
     // See the documentation of the free function with the same name.
     // it has the same requirements.
-    pub fn read_chunk_table_only(&mut self) -> PyResult<Py<PyAny>> {
-        Python::attach(|py| {
-            let uses_variable_chunk_size = self.decompressor.vlr().uses_variable_size_chunks();
-            let chunk_table = laz::laszip::ChunkTable::read(
-                self.decompressor.get_mut(),
-                uses_variable_chunk_size,
-            )
-            .map_err(into_py_err)?;
-            let elements = chunk_table
-                .as_ref()
-                .iter()
-                .map(|entry| (entry.point_count, entry.byte_count));
-            let list = PyList::new(py, elements)?;
-            Ok(list.into_any().unbind())
-        })
+    pub fn read_chunk_table_only(&mut self) -> PyResult<ChunkTable> {
+        let uses_variable_chunk_size = self.decompressor.vlr().uses_variable_size_chunks();
+        laz::laszip::ChunkTable::read(self.decompressor.get_mut(), uses_variable_chunk_size)
+            .map(ChunkTable::from)
+            .map_err(into_py_err)
     }
 
+    /// Reads the bytes at the position of the source as they are,
+    /// the layers that are not selected included.
     pub fn read_raw_bytes_into<'py>(&mut self, bytes: &Bound<'py, PyAny>) -> PyResult<()> {
-        let slc = as_mut_bytes(bytes)?;
+        let mut slc = as_mut_bytes(bytes)?;
         self.decompressor
             .get_mut()
-            .read_exact(slc)
+            .read_exact_raw(&mut slc)
             .map_err(into_py_err)
     }
+
+    /// The bytes read from the source, `None` when no layers are skipped.
+    ///
+    /// Layers are only skipped for the point formats 6 to 10,
+    /// when the decompressor is created with a selection and the source has a chunk table.
+    fn read_stats(&mut self) -> Option<ReadStats> {
+        selective::read_stats(self.decompressor.get_mut())
+    }
 }
 
 #[pyclass]
 struct LasZipCompressor {
-    compressor: laz::LasZipCompressor<'static, BufWriter<PyFileObject>>,
+    /// `None` once `done()` was called
+    compressor: Option<laz::LasZipCompressor<'static, Writer>>,
+    rollback: Rollback,
+}
+
+impl LasZipCompressor {
+    fn compressor(&mut self) -> PyResult<&mut laz::LasZipCompressor<'static, Writer>> {
+        self.compressor
+            .as_mut()
+            .ok_or_else(|| PyErr::new::<LazrsError, _>("The compressor is closed"))
+    }
+
+    /// Closes the compressor, and removes what it wrote from the destination
+    fn abort(&mut self) -> PyResult<()> {
+        let Some(compressor) = self.compressor.take() else {
+            return Ok(());
+        };
+        self.rollback
+            .apply(&mut compressor.into_inner())
+            .map_err(into_py_err)
+    }
 }
 
 #[pymethods]
 impl LasZipCompressor {
     #[new]
     pub fn new(dest: Py<PyAny>, vlr: &LazVlr) -> PyResult<Self> {
-        let dest = Python::attach(|py| PyFileObject::new(py, dest))?;
-        let dest = BufWriter::new(dest);
+        let mut dest = Python::attach(|py| Writer::new(py, dest))?;
+        let rollback = Rollback::compressor(&mut dest)?;
         let compressor = laz::LasZipCompressor::new(dest, vlr.vlr.clone()).map_err(into_py_err)?;
-        Ok(Self { compressor })
+        Ok(Self {
+            compressor: Some(compressor),
+            rollback,
+        })
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Calls `done()`, or removes what was written if an exception was raised
+    fn __exit__(
+        &mut self,
+        exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        if self.compressor.is_some() {
+            match exc_type {
+                None => {
+                    if let Err(error) = self.done() {
+                        return self.abort().and(Err(error));
+                    }
+                }
+                Some(_) => self.abort()?,
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether `done()` was called
+    #[getter]
+    fn closed(&self) -> bool {
+        self.compressor.is_none()
     }
 
     pub fn reserve_offset_to_chunk_table(&mut self) -> PyResult<()> {
-        self.compressor
+        let compressor = self.compressor()?;
+        compressor
             .reserve_offset_to_chunk_table()
             .map_err(into_py_err)?;
-        self.compressor.get_mut().flush().map_err(into_py_err)
+        compressor.get_mut().flush().map_err(into_py_err)
     }
 
-    pub fn compress_many<'py>(&mut self, points: &Bound<'py, PyAny>) -> PyResult<()> {
-        self.compressor
-            .compress_many(as_bytes(points)?)
-            .map_err(into_py_err)
+    /// Compresses the points.
+    ///
+    /// `progress` is called with `(points_done, total)` after each chunk.
+    #[pyo3(signature = (points, progress = None))]
+    pub fn compress_many<'py>(
+        &mut self,
+        py: Python<'py>,
+        points: &Bound<'py, PyAny>,
+        progress: Option<Py<PyAny>>,
+    ) -> PyResult<()> {
+        let point_bytes = as_bytes(points)?;
+        let compressor = self.compressor()?;
+        let vlr = compressor.vlr();
+        let batch_size = progress::batch_size(vlr, 1);
+        let mut progress =
+            Progress::new(progress, vlr, point_bytes.len() as u64 / vlr.items_size());
+        py.detach(|| {
+            progress.run(point_bytes.chunks(batch_size), |batch| {
+                compressor.compress_many(batch)
+            })
+        })
     }
 
+    /// Finishes the compression, the compressor can no longer be used afterwards
     pub fn done(&mut self) -> PyResult<()> {
-        self.compressor.done().map_err(into_py_err)?;
-        self.compressor.get_mut().flush().map_err(into_py_err)
+        let compressor = self.compressor()?;
+        compressor.done().map_err(into_py_err)?;
+        compressor.get_mut().flush().map_err(into_py_err)?;
+        self.compressor = None;
+        Ok(())
     }
 
-    pub fn compress_chunks<'py>(&mut self, chunks: &Bound<'py, PyList>) -> PyResult<()> {
+    pub fn compress_chunks<'py>(
+        &mut self,
+        py: Python<'py>,
+        chunks: &Bound<'py, PyList>,
+    ) -> PyResult<()> {
         for chunk in chunks.iter() {
-            self.compress_many(&chunk)?;
+            self.compress_many(py, &chunk, None)?;
             self.finish_current_chunk()?;
         }
         Ok(())
     }
 
     pub fn finish_current_chunk(&mut self) -> PyResult<()> {
-        self.compressor.finish_current_chunk().map_err(into_py_err)
+        self.compressor()?
+            .finish_current_chunk()
+            .map_err(into_py_err)
     }
 }
 
 #[pyfunction]
+#[pyo3(signature = (
+    compressed_points_data,
+    laszip_vlr_record_data,
+    decompression_output,
+    parallel,
+    num_threads = None,
+    thread_pool = None,
+    progress = None
+))]
+#[allow(clippy::too_many_arguments)]
 fn decompress_points<'py>(
+    py: Python<'py>,
     compressed_points_data: &Bound<'py, PyAny>,
     laszip_vlr_record_data: &Bound<'py, PyAny>,
     decompression_output: &Bound<'py, PyAny>,
     parallel: bool,
+    num_threads: Option<usize>,
+    thread_pool: Option<PyRef<'py, ThreadPool>>,
+    progress: Option<Py<PyAny>>,
 ) -> PyResult<()> {
+    let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
     let vlr_data = as_bytes(laszip_vlr_record_data)?;
     let data_slc = as_bytes(compressed_points_data)?;
-    let output = as_mut_bytes(decompression_output)?;
-
-    laz::LazVlr::read_from(vlr_data)
-        .and_then(|vlr| {
-            if !parallel {
-                laz::decompress_buffer(data_slc, output, vlr)
-            } else {
-                laz::par_decompress_buffer(data_slc, output, &vlr)
-            }
-        })
-        .map_err(into_py_err)?;
-    Ok(())
+    let mut output = as_mut_bytes(decompression_output)?;
+
+    let vlr = laz::LazVlr::read_from(&*vlr_data).map_err(into_py_err)?;
+    let mut progress = Progress::new(progress, &vlr, output.len() as u64 / vlr.items_size());
+    py.detach(|| {
+        let source = std::io::Cursor::new(&*data_slc);
+        if !parallel {
+            let batch_size = progress::batch_size(&vlr, 1);
+            let mut decompressor =
+                laz::LasZipDecompressor::new(source, vlr).map_err(into_py_err)?;
+            progress.run(output.chunks_mut(batch_size), |batch| {
+                decompressor.decompress_many(batch)
+            })
+        } else {
+            let batch_size = progress::batch_size(&vlr, pool.install(rayon::current_num_threads));
+            let mut decompressor =
+                laz::ParLasZipDecompressor::new(source, vlr).map_err(into_py_err)?;
+            progress.run(output.chunks_mut(batch_size), |batch| {
+                pool.install(|| decompressor.decompress_many(batch))
+            })
+        }
+    })
 }
 
 #[pyfunction]
@@ -358,67 +1054,319 @@ fn decompress_points<'py>(
     laszip_vlr_record_data,
     decompression_output,
     py_chunk_table,
-    selection = None
+    selection = None,
+    num_threads = None,
+    thread_pool = None
 ))]
+#[allow(clippy::too_many_arguments)]
 fn decompress_points_with_chunk_table<'py>(
+    py: Python<'py>,
     compressed_points_data: &Bound<'py, PyAny>,
     laszip_vlr_record_data: &Bound<'py, PyAny>,
     decompression_output: &Bound<'py, PyAny>,
-    py_chunk_table: &Bound<'py, PyList>,
+    py_chunk_table: &Bound<'py, PyAny>,
     selection: Option<DecompressionSelection>,
+    num_threads: Option<usize>,
+    thread_pool: Option<PyRef<'py, ThreadPool>>,
 ) -> PyResult<()> {
+    let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
     let vlr_data = as_bytes(laszip_vlr_record_data)?;
     let data_slc = as_bytes(compressed_points_data)?;
-    let output = as_mut_bytes(decompression_output)?;
-    let chunk_table = chunk_table_from_py_list(py_chunk_table)?;
-
-    laz::LazVlr::read_from(vlr_data)
-        .and_then(|vlr| {
+    let chunk_table = chunk_table_from_py(py_chunk_table)?;
+
+    let vlr = laz::LazVlr::read_from(&*vlr_data).map_err(into_py_err)?;
+    if let Ok(columns) = decompression_output.cast::<PyDict>() {
+        let selection = selection.map_or_else(laz::DecompressionSelection::all, |s| s.0);
+        let mut columns = columns::Columns::from_dict(&vlr, selection, columns)?;
+        return py
+            .detach(|| {
+                pool.install(|| {
+                    columns.par_decompress_chunks(&data_slc, &vlr, chunk_table.as_ref(), selection)
+                })
+            })
+            .map_err(into_py_err);
+    }
+    let mut output = as_mut_bytes(decompression_output)?;
+    py.detach(|| {
+        pool.install(|| {
             if let Some(selection) = selection {
                 laz::par_decompress_selective(
-                    data_slc,
-                    output,
+                    &data_slc,
+                    &mut output,
                     &vlr,
                     chunk_table.as_ref(),
                     selection.0,
                 )
             } else {
-                laz::par_decompress(data_slc, output, &vlr, chunk_table.as_ref())
+                laz::par_decompress(&data_slc, &mut output, &vlr, chunk_table.as_ref())
             }
         })
-        .map_err(into_py_err)?;
+    })
+    .map_err(into_py_err)?;
 
     Ok(())
 }
 
+/// Decompresses the `count` points starting at `start` into `decompression_output`.
+///
+/// Only the chunks that contain the points are decompressed, in parallel.
+/// As for `decompress_points_with_chunk_table`, `compressed_points_data`
+/// must start at the first chunk.
+///
+/// `point_count` is the number of points of the chunks (the point count of the
+/// LAS header), it must be given with fixed size chunks for the point formats < 6,
+/// which do not store the number of points of the last chunk.
+#[pyfunction]
+#[pyo3(signature = (
+    compressed_points_data,
+    laszip_vlr_record_data,
+    chunk_table,
+    start,
+    count,
+    decompression_output,
+    selection = None,
+    point_count = None,
+    num_threads = None,
+    thread_pool = None
+))]
+#[allow(clippy::too_many_arguments)]
+fn decompress_range<'py>(
+    py: Python<'py>,
+    compressed_points_data: &Bound<'py, PyAny>,
+    laszip_vlr_record_data: &Bound<'py, PyAny>,
+    chunk_table: &Bound<'py, PyAny>,
+    start: u64,
+    count: u64,
+    decompression_output: &Bound<'py, PyAny>,
+    selection: Option<DecompressionSelection>,
+    point_count: Option<u64>,
+    num_threads: Option<usize>,
+    thread_pool: Option<PyRef<'py, ThreadPool>>,
+) -> PyResult<()> {
+    let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
+    let vlr = laz::LazVlr::read_from(&*as_bytes(laszip_vlr_record_data)?).map_err(into_py_err)?;
+    let data_slc = as_bytes(compressed_points_data)?;
+    let mut output = as_mut_bytes(decompression_output)?;
+    let chunk_table = chunk_table_from_py(chunk_table)?;
+    let selection = selection.map_or_else(laz::DecompressionSelection::all, |s| s.0);
+
+    let num_bytes = partial::check_output_len(output.len(), count, &vlr)?;
+    py.detach(|| {
+        let point_count = match point_count {
+            Some(point_count) => point_count,
+            None => recovery::table_point_count(
+                &mut std::io::Cursor::new(&*data_slc),
+                0,
+                chunk_table.as_ref(),
+                &vlr,
+            )?,
+        };
+        let chunks = partial::covering_chunks(chunk_table.as_ref(), point_count, start, count)?;
+        let compressed = usize::try_from(chunks.byte_offset)
+            .ok()
+            .and_then(|offset| data_slc.get(offset..))
+            .ok_or_else(|| PyErr::new::<LazrsError, _>("The compressed data is too short"))?;
+        pool.install(|| {
+            partial::par_decompress_range(
+                compressed,
+                &vlr,
+                &chunk_table.as_ref()[chunks.indices.clone()],
+                chunks.skip,
+                &mut output[..num_bytes],
+                selection,
+            )
+        })
+        .map_err(into_py_err)
+    })
+}
+
+/// Decompresses the chunks at the given `indices`, one after the other, into `decompression_output`.
+///
+/// `source` is either the compressed points data (starting at the first chunk),
+/// or a file object positioned at the start of the points data (or a path).
+///
+/// Returns the index in the output of the first point of each chunk.
+///
+/// With fixed size chunks, the last entry of the table says the chunk is full,
+/// the number of points of the last chunk comes from `point_count` (the point count
+/// of the LAS header), which must be given to decompress the last chunk
+/// for the point formats < 6, as they do not store it.
+#[pyfunction]
+#[pyo3(signature = (
+    source,
+    vlr,
+    chunk_table,
+    indices,
+    decompression_output,
+    selection = None,
+    point_count = None,
+    num_threads = None,
+    thread_pool = None
+))]
+#[allow(clippy::too_many_arguments)]
+fn decompress_chunks<'py>(
+    py: Python<'py>,
+    source: &Bound<'py, PyAny>,
+    vlr: &LazVlr,
+    chunk_table: &Bound<'py, PyAny>,
+    indices: Vec<usize>,
+    decompression_output: &Bound<'py, PyAny>,
+    selection: Option<DecompressionSelection>,
+    point_count: Option<u64>,
+    num_threads: Option<usize>,
+    thread_pool: Option<PyRef<'py, ThreadPool>>,
+) -> PyResult<Vec<u64>> {
+    let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
+    let chunk_table = chunk_table_from_py(chunk_table)?;
+    let mut output = as_mut_bytes(decompression_output)?;
+    let selection = selection.map_or_else(laz::DecompressionSelection::all, |s| s.0);
+    let entries = chunk_table.as_ref();
+    if let Some(index) = indices.iter().find(|&&index| index >= entries.len()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyIndexError, _>(format!(
+            "chunk index {} is out of range, the table has {} chunks",
+            index,
+            entries.len()
+        )));
+    }
+
+    let fixed_size = !vlr.vlr.uses_variable_size_chunks();
+    let last_index = entries.len().checked_sub(1);
+    let needs_point_count = fixed_size
+        && point_count.is_none()
+        && last_index.is_some_and(|last| indices.contains(&last));
+
+    let (compressed, mut selected_entries, point_count) = if let Ok(data) = as_bytes(source) {
+        let point_count = if needs_point_count {
+            let mut data = std::io::Cursor::new(&*data);
+            Some(recovery::table_point_count(
+                &mut data, 0, entries, &vlr.vlr,
+            )?)
+        } else {
+            point_count
+        };
+        let (compressed, selected_entries) =
+            partial::gather_chunks(entries, &indices, |offset, chunk| {
+                let offset = offset as usize;
+                data.get(offset..offset + chunk.len())
+                    .map(|bytes| chunk.copy_from_slice(bytes))
+                    .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+            })?;
+        (compressed, selected_entries, point_count)
+    } else {
+        let mut source = Reader::new(py, source.clone().unbind())?;
+        // Skip the offset to the chunk table
+        let first_chunk_start = source.stream_position()? + std::mem::size_of::<i64>() as u64;
+        py.detach(|| {
+            let point_count = if needs_point_count {
+                Some(recovery::table_point_count(
+                    &mut source,
+                    first_chunk_start,
+                    entries,
+                    &vlr.vlr,
+                )?)
+            } else {
+                point_count
+            };
+            let (compressed, selected_entries) =
+                partial::gather_chunks(entries, &indices, |offset, chunk| {
+                    source.seek(std::io::SeekFrom::Start(first_chunk_start + offset))?;
+                    source.read_exact(chunk)
+                })?;
+            PyResult::Ok((compressed, selected_entries, point_count))
+        })?
+    };
+
+    if fixed_size {
+        // Only the last chunk may not be full
+        if let Some(point_count) = point_count {
+            chunk_iter::check_point_count(entries, &vlr.vlr, point_count)?;
+        }
+        let chunk_size = u64::from(vlr.vlr.chunk_size());
+        for (entry, &index) in selected_entries.iter_mut().zip(&indices) {
+            entry.point_count = match point_count {
+                Some(point_count) if Some(index) == last_index => {
+                    point_count - index as u64 * chunk_size
+                }
+                _ => chunk_size,
+            };
+        }
+    }
+    let mut point_offsets = Vec::with_capacity(selected_entries.len());
+    let mut num_points = 0u64;
+    for entry in &selected_entries {
+        point_offsets.push(num_points);
+        num_points += entry.point_count;
+    }
+    let num_bytes = partial::check_output_len(output.len(), num_points, &vlr.vlr)?;
+    let output = &mut output[..num_bytes];
+
+    py.detach(|| {
+        pool.install(|| {
+            laz::par_decompress_selective(
+                &compressed,
+                output,
+                &vlr.vlr,
+                &selected_entries,
+                selection,
+            )
+        })
+    })
+    .map_err(into_py_err)?;
+    Ok(point_offsets)
+}
+
 #[pyfunction]
+#[pyo3(signature = (
+    laszip_vlr,
+    uncompressed_points,
+    parallel,
+    num_threads = None,
+    thread_pool = None,
+    progress = None
+))]
 fn compress_points<'py>(
+    py: Python<'py>,
     laszip_vlr: &LazVlr,
     uncompressed_points: &Bound<'py, PyAny>,
     parallel: bool,
+    num_threads: Option<usize>,
+    thread_pool: Option<PyRef<'py, ThreadPool>>,
+    progress: Option<Py<PyAny>>,
 ) -> PyResult<Py<PyAny>> {
+    let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
     let mut compression_result = std::io::Cursor::new(Vec::<u8>::new());
-    if !parallel {
-        laz::compress_buffer(
-            &mut compression_result,
-            as_bytes(uncompressed_points)?,
-            laszip_vlr.vlr.clone(),
-        )
-        .map_err(|e| PyErr::new::<LazrsError, String>(format!("{}", e)))?;
-    } else {
-        laz::par_compress_buffer(
-            &mut compression_result,
-            as_bytes(uncompressed_points)?,
-            &laszip_vlr.vlr,
-        )
-        .map_err(|e| PyErr::new::<LazrsError, String>(format!("{}", e)))?;
-    }
-    Python::attach(|py| {
-        let bytes = PyBytes::new(py, compression_result.get_ref())
-            .into_any()
-            .unbind();
-        Ok(bytes)
-    })
+    let point_bytes = as_bytes(uncompressed_points)?;
+    let vlr = &laszip_vlr.vlr;
+    let mut progress = Progress::new(progress, vlr, point_bytes.len() as u64 / vlr.items_size());
+    py.detach(|| {
+        if !parallel {
+            let mut compressor = laz::LasZipCompressor::new(&mut compression_result, vlr.clone())
+                .map_err(into_py_err)?;
+            progress.run(point_bytes.chunks(progress::batch_size(vlr, 1)), |batch| {
+                compressor.compress_many(batch)
+            })?;
+            compressor.done().map_err(into_py_err)
+        } else if vlr.uses_variable_size_chunks() {
+            // The points are not split in chunks, they are compressed at once
+            pool.install(|| laz::par_compress_buffer(&mut compression_result, &point_bytes, vlr))
+                .map_err(into_py_err)?;
+            progress.advance(point_bytes.len())
+        } else {
+            let batch_size = progress::batch_size(vlr, pool.install(rayon::current_num_threads));
+            let mut compressor =
+                laz::ParLasZipCompressor::new(&mut compression_result, vlr.clone())
+                    .map_err(into_py_err)?;
+            progress.run(point_bytes.chunks(batch_size), |batch| {
+                pool.install(|| compressor.compress_many(batch))
+            })?;
+            pool.install(|| compressor.done()).map_err(into_py_err)
+        }
+    })?;
+
+    let bytes = PyBytes::new(py, compression_result.get_ref())
+        .into_any()
+        .unbind();
+    Ok(bytes)
 }
 
 /// This reads the chunks table.
@@ -430,18 +1378,13 @@ fn compress_points<'py>(
 ///
 /// The `source` position **must** be at the beginning of the points data
 #[pyfunction]
-fn read_chunk_table(source: Py<PyAny>, vlr: &LazVlr) -> PyResult<Py<PyAny>> {
+fn read_chunk_table(source: Py<PyAny>, vlr: &LazVlr) -> PyResult<ChunkTable> {
     Python::attach(|py| {
         let mut src = BufReader::new(PyFileObject::new(py, source)?);
 
-        let chunk_table =
-            laz::laszip::ChunkTable::read_from(&mut src, &vlr.vlr).map_err(into_py_err)?;
-        let elements = chunk_table
-            .as_ref()
-            .iter()
-            .map(|entry| (entry.point_count, entry.byte_count));
-        let list = PyList::new(py, elements)?;
-        Ok(list.into_any().unbind())
+        laz::laszip::ChunkTable::read_from(&mut src, &vlr.vlr)
+            .map(ChunkTable::from)
+            .map_err(into_py_err)
     })
 }
 
@@ -453,43 +1396,23 @@ fn read_chunk_table(source: Py<PyAny>, vlr: &LazVlr) -> PyResult<Py<PyAny>> {
 ///
 /// The `source` position **must** be at the beginning of the chunk table
 #[pyfunction]
-fn read_chunk_table_only(source: Py<PyAny>, vlr: &LazVlr) -> PyResult<Py<PyAny>> {
+fn read_chunk_table_only(source: Py<PyAny>, vlr: &LazVlr) -> PyResult<ChunkTable> {
     Python::attach(|py| {
         let mut src = BufReader::new(PyFileObject::new(py, source)?);
 
-        let chunk_table = laz::laszip::ChunkTable::read(&mut src, vlr.uses_variable_size_chunks())
-            .map_err(into_py_err)?;
-        let elements = chunk_table
-            .as_ref()
-            .iter()
-            .map(|entry| (entry.point_count, entry.byte_count));
-        let list = PyList::new(py, elements)?;
-        Ok(list.into_any().unbind())
+        laz::laszip::ChunkTable::read(&mut src, vlr.uses_variable_size_chunks())
+            .map(ChunkTable::from)
+            .map_err(into_py_err)
     })
 }
 
-fn chunk_table_from_py_list<'py>(
-    py_chunk_table: &Bound<'py, PyList>,
-) -> PyResult<laz::laszip::ChunkTable> {
-    let mut chunk_table = laz::laszip::ChunkTable::with_capacity(py_chunk_table.len());
-
-    for object in py_chunk_table.iter() {
-        let (point_count, byte_count): (u64, u64) = object.extract()?;
-        chunk_table.push(laz::laszip::ChunkTableEntry {
-            point_count,
-            byte_count,
-        });
-    }
-    Ok(chunk_table)
-}
-
 #[pyfunction]
 fn write_chunk_table<'py>(
     dest: Py<PyAny>,
-    py_chunk_table: &Bound<'py, PyList>,
+    py_chunk_table: &Bound<'py, PyAny>,
     vlr: &LazVlr,
 ) -> PyResult<()> {
-    let chunk_table = chunk_table_from_py_list(py_chunk_table)?;
+    let chunk_table = chunk_table_from_py(py_chunk_table)?;
 
     let dest = Python::attach(|py| PyFileObject::new(py, dest).map(BufWriter::new))?;
     chunk_table.write_to(dest, &vlr.vlr).map_err(into_py_err)
@@ -497,100 +1420,330 @@ fn write_chunk_table<'py>(
 
 #[pyclass]
 struct ParLasZipAppender {
-    appender: laz::ParLasZipAppender<BufReadWritePyFileObject>,
+    /// `None` once `done()` was called
+    appender: Option<append::ParAppender>,
+    vlr: laz::LazVlr,
+    /// Position of the start of the point data
+    data_start: u64,
+    /// Number of points, with the ones appended so far
+    point_count: u64,
+    rollback: Rollback,
+    pool: Pool,
+}
+
+impl ParLasZipAppender {
+    fn appender(&mut self) -> PyResult<&mut append::ParAppender> {
+        self.appender
+            .as_mut()
+            .ok_or_else(|| PyErr::new::<LazrsError, _>("The appender is closed"))
+    }
+
+    /// Closes the appender, and restores the destination to its original content
+    fn abort(&mut self) -> PyResult<()> {
+        let Some(appender) = self.appender.take() else {
+            return Ok(());
+        };
+        self.rollback
+            .apply(&mut appender.into_inner())
+            .map_err(into_py_err)
+    }
 }
 
 #[pymethods]
 impl ParLasZipAppender {
+    /// `point_count` is the number of points `dest` already has, it is checked
+    /// against its chunk table, which also detects most VLRs that are not the one of `dest`.
     #[new]
+    #[pyo3(signature = (dest, laz_vlr_record_data, point_count, num_threads = None, thread_pool = None))]
     fn new<'py>(
         dest: Py<PyAny>,
         laz_vlr_record_data: &Bound<'py, PyAny>,
         point_count: u64,
+        num_threads: Option<usize>,
+        thread_pool: Option<PyRef<'py, ThreadPool>>,
     ) -> PyResult<Self> {
-        let data =
-            Python::attach(|py| PyFileObject::new(py, dest).map(BufReadWritePyFileObject::new))?;
-        let vlr = laz::LazVlr::read_from(as_bytes(laz_vlr_record_data)?).map_err(into_py_err)?;
-        let appender = laz::ParLasZipAppender::new(data, vlr, point_count).map_err(into_py_err)?;
-        Ok(ParLasZipAppender { appender })
+        let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
+        let mut data = Python::attach(|py| ReadWriter::new(py, dest))?;
+        let vlr = laz::LazVlr::read_from(&*as_bytes(laz_vlr_record_data)?).map_err(into_py_err)?;
+        let data_start = data.stream_position()?;
+        append::check_chunk_table(&mut data, &vlr, point_count)?;
+        let rollback = Rollback::appender(&mut data, &vlr, point_count).map_err(into_py_err)?;
+        let appender =
+            append::ParAppender::new(data, vlr.clone(), point_count).map_err(into_py_err)?;
+        Ok(ParLasZipAppender {
+            appender: Some(appender),
+            vlr,
+            data_start,
+            point_count,
+            rollback,
+            pool,
+        })
     }
 
-    fn compress_many<'py>(&mut self, points: &Bound<'py, PyAny>) -> PyResult<()> {
-        let point_bytes = as_bytes(points)?;
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
 
-        self.appender
-            .compress_many(point_bytes)
-            .map_err(into_py_err)
+    /// Calls `done()`, or restores the destination if an exception was raised
+    fn __exit__(
+        &mut self,
+        py: Python,
+        exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        if self.appender.is_some() {
+            match exc_type {
+                None => {
+                    if let Err(error) = self.done(py) {
+                        return self.abort().and(Err(error));
+                    }
+                }
+                Some(_) => self.abort()?,
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether `done()` was called
+    #[getter]
+    fn closed(&self) -> bool {
+        self.appender.is_none()
+    }
+
+    /// Compresses the points.
+    ///
+    /// `progress` is called with `(points_done, total)` after each batch of chunks.
+    #[pyo3(signature = (points, progress = None))]
+    fn compress_many<'py>(
+        &mut self,
+        py: Python<'py>,
+        points: &Bound<'py, PyAny>,
+        progress: Option<Py<PyAny>>,
+    ) -> PyResult<()> {
+        append::check_chunk_kind(&self.vlr, false)?;
+        let point_bytes = as_bytes(points)?;
+        let num_points = append::num_points(&point_bytes, &self.vlr)?;
+
+        let batch_size =
+            progress::batch_size(&self.vlr, self.pool.install(rayon::current_num_threads));
+        let mut progress = Progress::new(progress, &self.vlr, num_points);
+        let pool = self.pool.clone();
+        let appender = self.appender()?;
+        py.detach(|| {
+            progress.run(point_bytes.chunks(batch_size), |batch| {
+                pool.install(|| appender.compress_many(batch))
+            })
+        })?;
+        self.point_count += num_points;
+        Ok(())
     }
 
-    pub fn compress_chunks<'py>(&mut self, chunks: &Bound<'py, PyList>) -> PyResult<()> {
+    pub fn compress_chunks<'py>(
+        &mut self,
+        py: Python<'py>,
+        chunks: &Bound<'py, PyList>,
+    ) -> PyResult<()> {
         let chunks = chunks
             .iter()
             .map(|chunk| as_bytes(&chunk))
-            .collect::<PyResult<Vec<&[u8]>>>()?;
-        self.appender.compress_chunks(chunks)?;
+            .collect::<PyResult<Vec<Bytes>>>()?;
+        let num_points = chunks
+            .iter()
+            .map(|chunk| append::num_points(chunk, &self.vlr))
+            .sum::<PyResult<u64>>()?;
+        append::check_chunk_kind(&self.vlr, true)?;
+        let pool = self.pool.clone();
+        let appender = self.appender()?;
+        py.detach(|| pool.install(|| appender.compress_chunks(chunks)))?;
+        self.point_count += num_points;
         Ok(())
     }
 
-    fn done(&mut self) -> PyResult<()> {
-        self.appender.done().map_err(into_py_err)?;
-        self.appender.get_mut().flush().map_err(into_py_err)
+    /// Finishes the compression, the appender can no longer be used afterwards.
+    ///
+    /// Returns the new number of points and the new chunk table,
+    /// to update the LAS header.
+    fn done(&mut self, py: Python) -> PyResult<(u64, ChunkTable)> {
+        let pool = self.pool.clone();
+        let (data_start, point_count) = (self.data_start, self.point_count);
+        let vlr = self.vlr.clone();
+        let appender = self.appender()?;
+        py.detach(|| pool.install(|| appender.done()))
+            .map_err(into_py_err)?;
+        appender.get_mut().flush().map_err(into_py_err)?;
+        let chunk_table =
+            append::appended_chunk_table(appender.get_mut(), data_start, &vlr, point_count)?;
+        self.appender = None;
+        Ok((point_count, chunk_table))
     }
 }
 
 #[pyclass]
 struct LasZipAppender {
-    appender: laz::LasZipAppender<'static, BufReadWritePyFileObject>,
+    /// `None` once `done()` was called
+    appender: Option<laz::LasZipAppender<'static, ReadWriter>>,
+    vlr: laz::LazVlr,
+    /// Position of the start of the point data
+    data_start: u64,
+    /// Number of points, with the ones appended so far
+    point_count: u64,
+    rollback: Rollback,
+}
+
+impl LasZipAppender {
+    fn appender(&mut self) -> PyResult<&mut laz::LasZipAppender<'static, ReadWriter>> {
+        self.appender
+            .as_mut()
+            .ok_or_else(|| PyErr::new::<LazrsError, _>("The appender is closed"))
+    }
+
+    /// Closes the appender, and restores the destination to its original content
+    fn abort(&mut self) -> PyResult<()> {
+        let Some(appender) = self.appender.take() else {
+            return Ok(());
+        };
+        self.rollback
+            .apply(&mut appender.into_inner())
+            .map_err(into_py_err)
+    }
 }
 
 #[pymethods]
 impl LasZipAppender {
+    /// `point_count` is the number of points `dest` already has, it is checked
+    /// against its chunk table, which also detects most VLRs that are not the one of `dest`.
     #[new]
     fn new<'py>(
         dest: Py<PyAny>,
         laz_vlr_record_data: &Bound<'py, PyAny>,
         point_count: u64,
     ) -> PyResult<Self> {
-        let data =
-            Python::attach(|py| PyFileObject::new(py, dest).map(BufReadWritePyFileObject::new))?;
-        let vlr = laz::LazVlr::read_from(as_bytes(laz_vlr_record_data)?).map_err(into_py_err)?;
-        let appender = laz::LasZipAppender::new(data, vlr, point_count).map_err(into_py_err)?;
-        Ok(LasZipAppender { appender })
+        let mut data = Python::attach(|py| ReadWriter::new(py, dest))?;
+        let vlr = laz::LazVlr::read_from(&*as_bytes(laz_vlr_record_data)?).map_err(into_py_err)?;
+        let data_start = data.stream_position()?;
+        append::check_chunk_table(&mut data, &vlr, point_count)?;
+        let rollback = Rollback::appender(&mut data, &vlr, point_count).map_err(into_py_err)?;
+        let appender =
+            laz::LasZipAppender::new(data, vlr.clone(), point_count).map_err(into_py_err)?;
+        Ok(LasZipAppender {
+            appender: Some(appender),
+            vlr,
+            data_start,
+            point_count,
+            rollback,
+        })
     }
 
-    fn compress_many<'py>(&mut self, points: &Bound<'py, PyAny>) -> PyResult<()> {
-        let point_bytes = as_bytes(points)?;
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
 
-        self.appender
-            .compress_many(point_bytes)
-            .map_err(into_py_err)
+    /// Calls `done()`, or restores the destination if an exception was raised
+    fn __exit__(
+        &mut self,
+        exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        if self.appender.is_some() {
+            match exc_type {
+                None => {
+                    if let Err(error) = self.done() {
+                        return self.abort().and(Err(error));
+                    }
+                }
+                Some(_) => self.abort()?,
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether `done()` was called
+    #[getter]
+    fn closed(&self) -> bool {
+        self.appender.is_none()
     }
 
-    pub fn compress_chunks<'py>(&mut self, chunks: &Bound<'py, PyList>) -> PyResult<()> {
+    /// Compresses the points.
+    ///
+    /// `progress` is called with `(points_done, total)` after each chunk.
+    #[pyo3(signature = (points, progress = None))]
+    fn compress_many<'py>(
+        &mut self,
+        py: Python<'py>,
+        points: &Bound<'py, PyAny>,
+        progress: Option<Py<PyAny>>,
+    ) -> PyResult<()> {
+        let point_bytes = as_bytes(points)?;
+        let num_points = append::num_points(&point_bytes, &self.vlr)?;
+
+        let batch_size = progress::batch_size(&self.vlr, 1);
+        let mut progress = Progress::new(progress, &self.vlr, num_points);
+        let appender = self.appender()?;
+        py.detach(|| {
+            progress.run(point_bytes.chunks(batch_size), |batch| {
+                appender.compress_many(batch)
+            })
+        })?;
+        self.point_count += num_points;
+        Ok(())
+    }
+
+    pub fn compress_chunks<'py>(
+        &mut self,
+        py: Python<'py>,
+        chunks: &Bound<'py, PyList>,
+    ) -> PyResult<()> {
         let chunks = chunks
             .iter()
             .map(|chunk| as_bytes(&chunk))
-            .collect::<PyResult<Vec<&[u8]>>>()?;
-        self.appender.compress_chunks(chunks)?;
+            .collect::<PyResult<Vec<Bytes>>>()?;
+        let num_points = chunks
+            .iter()
+            .map(|chunk| append::num_points(chunk, &self.vlr))
+            .sum::<PyResult<u64>>()?;
+        let appender = self.appender()?;
+        py.detach(|| appender.compress_chunks(chunks))?;
+        self.point_count += num_points;
         Ok(())
     }
 
-    fn done(&mut self) -> PyResult<()> {
-        self.appender.done().map_err(into_py_err)?;
-        self.appender.get_mut().flush().map_err(into_py_err)
+    /// Finishes the compression, the appender can no longer be used afterwards.
+    ///
+    /// Returns the new number of points and the new chunk table,
+    /// to update the LAS header.
+    fn done(&mut self) -> PyResult<(u64, ChunkTable)> {
+        let (data_start, point_count) = (self.data_start, self.point_count);
+        let vlr = self.vlr.clone();
+        let appender = self.appender()?;
+        appender.done().map_err(into_py_err)?;
+        appender.get_mut().flush().map_err(into_py_err)?;
+        let chunk_table =
+            append::appended_chunk_table(appender.get_mut(), data_start, &vlr, point_count)?;
+        self.appender = None;
+        Ok((point_count, chunk_table))
     }
 }
 
 /// This module is a python module implemented in Rust.
 #[pymodule]
-fn lazrs<'py>(py: Python, m: &Bound<'py, PyModule>) -> PyResult<()> {
+fn lazrs<'py>(_py: Python, m: &Bound<'py, PyModule>) -> PyResult<()> {
     m.add_wrapped(wrap_pyfunction!(decompress_points))?;
     m.add_wrapped(wrap_pyfunction!(compress_points))?;
     m.add_wrapped(wrap_pyfunction!(read_chunk_table))?;
     m.add_wrapped(wrap_pyfunction!(read_chunk_table_only))?;
     m.add_wrapped(wrap_pyfunction!(write_chunk_table))?;
     m.add_wrapped(wrap_pyfunction!(decompress_points_with_chunk_table))?;
-    m.add("LazrsError", py.get_type::<LazrsError>())?;
+    m.add_wrapped(wrap_pyfunction!(decompress_range))?;
+    m.add_wrapped(wrap_pyfunction!(decompress_chunks))?;
+    m.add_wrapped(wrap_pyfunction!(threads::set_num_threads))?;
+    m.add_wrapped(wrap_pyfunction!(threads::get_num_threads))?;
+    m.add_wrapped(wrap_pyfunction!(validate::validate))?;
+    m.add_wrapped(wrap_pyfunction!(recovery::recover_points))?;
+    m.add_wrapped(wrap_pyfunction!(recovery::repair_point_data))?;
+    m.add_wrapped(wrap_pyfunction!(transcode::transcode))?;
+    errors::register(m)?;
     m.add_class::<LazVlr>()?;
     m.add_class::<LasZipDecompressor>()?;
     m.add_class::<LasZipCompressor>()?;
@@ -599,6 +1752,18 @@ fn lazrs<'py>(py: Python, m: &Bound<'py, PyModule>) -> PyResult<()> {
     m.add_class::<ParLasZipDecompressor>()?;
     m.add_class::<ParLasZipAppender>()?;
     m.add_class::<DecompressionSelection>()?;
+    m.add_class::<ChunkTable>()?;
+    m.add_class::<ChunkIterator>()?;
+    m.add_class::<ThreadPool>()?;
+    m.add_class::<las::LasHeader>()?;
+    m.add_class::<las::Vlr>()?;
+    m.add_class::<reader::LazReader>()?;
+    m.add_class::<writer::LazWriter>()?;
+    m.add_class::<validate::ValidationReport>()?;
+    m.add_class::<validate::BadChunk>()?;
+    m.add_class::<vlr::LazItem>()?;
+    m.add_class::<convert::PointConverter>()?;
+    m.add_class::<ReadStats>()?;
 
     m.add(
         "SELECTIVE_DECOMPRESS_XY_RETURNS_CHANNEL",
@@ -0,0 +1,315 @@
+//! Decompression of a part of the points, using the chunk table
+//! to only decompress the chunks that contain the points.
+use std::io::{Read, Seek, SeekFrom};
+
+use laz::laszip::{ChunkTable, ChunkTableEntry};
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use crate::recovery::table_point_count;
+use crate::threads::Pool;
+use crate::{buffer_length_error, into_py_err, LazrsError};
+
+/// The chunks that contain a range of points
+pub(crate) struct CoveringChunks {
+    /// Indices of the chunks in the table
+    pub(crate) indices: std::ops::Range<usize>,
+    /// Number of points to skip in the first chunk
+    pub(crate) skip: u64,
+    /// Offset of the first chunk, relative to the start of the first chunk of the table
+    pub(crate) byte_offset: u64,
+    /// Number of compressed bytes of the chunks
+    pub(crate) byte_count: u64,
+}
+
+/// Finds the chunks that contain the points in `start..start + count`,
+/// `point_count` is the real number of points of the chunks (see `table_point_count`)
+pub(crate) fn covering_chunks(
+    entries: &[ChunkTableEntry],
+    point_count: u64,
+    start: u64,
+    count: u64,
+) -> PyResult<CoveringChunks> {
+    let end = start.checked_add(count).filter(|end| *end <= point_count);
+    let Some(end) = end else {
+        return Err(PyErr::new::<LazrsError, _>(format!(
+            "The point range of {} points starting at {} is out of the {} points of the chunks",
+            count, start, point_count
+        )));
+    };
+
+    let mut chunks = CoveringChunks {
+        indices: 0..0,
+        skip: 0,
+        byte_offset: 0,
+        byte_count: 0,
+    };
+    if count == 0 {
+        return Ok(chunks);
+    }
+    let mut chunk_start = 0u64;
+    let mut byte_offset = 0u64;
+    for (index, entry) in entries.iter().enumerate() {
+        let chunk_end = chunk_start + entry.point_count;
+        if chunk_end <= start {
+            byte_offset += entry.byte_count;
+        } else if chunk_start < end {
+            if chunks.indices.is_empty() {
+                chunks.indices = index..index;
+                chunks.skip = start - chunk_start;
+                chunks.byte_offset = byte_offset;
+            }
+            chunks.indices.end = index + 1;
+            chunks.byte_count += entry.byte_count;
+        } else {
+            break;
+        }
+        chunk_start = chunk_end;
+    }
+    Ok(chunks)
+}
+
+/// Decompresses the points of the chunks, in parallel.
+///
+/// `compressed_points` must start at the first chunk of `entries`,
+/// the first `skip` points of the first chunk are not copied in `out`,
+/// and decompression stops once `out` is full.
+pub(crate) fn par_decompress_range(
+    compressed_points: &[u8],
+    vlr: &laz::LazVlr,
+    entries: &[ChunkTableEntry],
+    skip: u64,
+    out: &mut [u8],
+    selection: laz::DecompressionSelection,
+) -> laz::Result<()> {
+    let point_size = vlr.items_size() as usize;
+
+    let mut jobs = Vec::with_capacity(entries.len());
+    let mut compressed = compressed_points;
+    let mut out = out;
+    let mut skip = skip as usize;
+    for entry in entries {
+        if out.is_empty() {
+            break;
+        }
+        let byte_count = entry.byte_count as usize;
+        if compressed.len() < byte_count {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        let (chunk_data, rest) = compressed.split_at(byte_count);
+        compressed = rest;
+
+        let num_points = std::cmp::min(entry.point_count as usize - skip, out.len() / point_size);
+        let (chunk_out, rest) = std::mem::take(&mut out).split_at_mut(num_points * point_size);
+        out = rest;
+        jobs.push((chunk_data, skip, num_points, chunk_out));
+        skip = 0;
+    }
+    if !out.is_empty() {
+        // The chunks hold fewer points than the range (the point count is wrong)
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+
+    jobs.into_par_iter()
+        .try_for_each(|(chunk_data, skip, num_points, chunk_out)| {
+            let entry = ChunkTableEntry {
+                point_count: (skip + num_points) as u64,
+                byte_count: chunk_data.len() as u64,
+            };
+            if skip == 0 {
+                laz::par_decompress_selective(chunk_data, chunk_out, vlr, &[entry], selection)
+            } else {
+                // The skipped points have to be decompressed too
+                let mut points = vec![0u8; (skip + num_points) * point_size];
+                laz::par_decompress_selective(chunk_data, &mut points, vlr, &[entry], selection)?;
+                chunk_out.copy_from_slice(&points[skip * point_size..]);
+                Ok(())
+            }
+        })
+}
+
+/// Decompresses the points in `start..start + count` from a LAZ source.
+///
+/// `data_start` is the position of the start of the point data
+/// in the source (where the offset to the chunk table is).
+pub(crate) fn read_range<R: Read + Seek>(
+    source: &mut R,
+    data_start: u64,
+    vlr: &laz::LazVlr,
+    chunk_table: &ChunkTable,
+    chunks: &CoveringChunks,
+    out: &mut [u8],
+    selection: laz::DecompressionSelection,
+) -> laz::Result<()> {
+    let offset_to_chunk_table_size = std::mem::size_of::<i64>() as u64;
+    source.seek(SeekFrom::Start(
+        data_start + offset_to_chunk_table_size + chunks.byte_offset,
+    ))?;
+    let mut compressed_points = vec![0u8; chunks.byte_count as usize];
+    source.read_exact(&mut compressed_points)?;
+
+    par_decompress_range(
+        &compressed_points,
+        vlr,
+        &chunk_table.as_ref()[chunks.indices.clone()],
+        chunks.skip,
+        out,
+        selection,
+    )
+}
+
+/// Copies the compressed data of the chunks at `indices` one after the other.
+///
+/// `read_at(offset, buf)` must fill `buf` with the compressed data at `offset`,
+/// which is relative to the start of the first chunk of the table.
+/// Returns the data, and the table entries of the chunks.
+pub(crate) fn gather_chunks<F>(
+    entries: &[ChunkTableEntry],
+    indices: &[usize],
+    mut read_at: F,
+) -> std::io::Result<(Vec<u8>, Vec<ChunkTableEntry>)>
+where
+    F: FnMut(u64, &mut [u8]) -> std::io::Result<()>,
+{
+    let mut byte_offsets = Vec::with_capacity(entries.len());
+    let mut byte_offset = 0u64;
+    for entry in entries {
+        byte_offsets.push(byte_offset);
+        byte_offset += entry.byte_count;
+    }
+
+    let selected_entries = indices
+        .iter()
+        .map(|&index| entries[index])
+        .collect::<Vec<_>>();
+    let num_bytes = selected_entries
+        .iter()
+        .map(|entry| entry.byte_count as usize)
+        .sum::<usize>();
+    let mut compressed = vec![0u8; num_bytes];
+    let mut rest = compressed.as_mut_slice();
+    for &index in indices {
+        let (chunk, r) = rest.split_at_mut(entries[index].byte_count as usize);
+        read_at(byte_offsets[index], chunk)?;
+        rest = r;
+    }
+    Ok((compressed, selected_entries))
+}
+
+/// Checks that the output can hold `count` points, returns the number of bytes of the points
+pub(crate) fn check_output_len(len: usize, count: u64, vlr: &laz::LazVlr) -> PyResult<usize> {
+    let num_bytes = count
+        .checked_mul(vlr.items_size())
+        .and_then(|num_bytes| usize::try_from(num_bytes).ok())
+        .ok_or_else(|| {
+            PyErr::new::<LazrsError, _>(format!("{} points do not fit in memory", count))
+        })?;
+    if len < num_bytes {
+        return Err(buffer_length_error(
+            format!(
+                "The output buffer is too small ({} bytes) to hold {} points ({} bytes)",
+                len, count, num_bytes
+            ),
+            num_bytes,
+            len,
+        ));
+    }
+    Ok(num_bytes)
+}
+
+/// Decompression of point ranges for the decompressors,
+/// which read the chunk table on first use.
+pub(crate) struct RangeDecompressor {
+    /// Position of the start of the point data in the source
+    data_start: u64,
+    chunk_table: Option<ChunkTable>,
+    /// Real number of points of the chunks, found on first use
+    point_count: Option<u64>,
+    pool: Pool,
+}
+
+impl RangeDecompressor {
+    pub(crate) fn new(data_start: u64, pool: Pool) -> Self {
+        Self {
+            data_start,
+            chunk_table: None,
+            point_count: None,
+            pool,
+        }
+    }
+
+    /// Position of the first chunk in the source
+    pub(crate) fn first_chunk_position(&self) -> u64 {
+        self.data_start + std::mem::size_of::<i64>() as u64
+    }
+
+    /// Returns the chunk table, it is read from the source on first use
+    pub(crate) fn chunk_table<R: Read + Seek>(
+        &mut self,
+        source: &mut R,
+        vlr: &laz::LazVlr,
+    ) -> PyResult<&ChunkTable> {
+        if self.chunk_table.is_none() {
+            source.seek(SeekFrom::Start(self.data_start))?;
+            let chunk_table = ChunkTable::read_from(&mut *source, vlr).map_err(into_py_err)?;
+            self.chunk_table = Some(chunk_table);
+        }
+        Ok(self.chunk_table.as_ref().unwrap())
+    }
+
+    /// Returns the real number of points of the chunks, it is found on first use
+    pub(crate) fn point_count<R: Read + Seek>(
+        &mut self,
+        source: &mut R,
+        vlr: &laz::LazVlr,
+    ) -> PyResult<u64> {
+        if let Some(point_count) = self.point_count {
+            return Ok(point_count);
+        }
+        let first_chunk = self.first_chunk_position();
+        let chunk_table = self.chunk_table(source, vlr)?;
+        let point_count = table_point_count(source, first_chunk, chunk_table.as_ref(), vlr)?;
+        self.point_count = Some(point_count);
+        Ok(point_count)
+    }
+
+    /// Decompresses the `count` points starting at `start` into `out`,
+    /// `point_count` is the number of points of the chunks, if known
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn decompress_range<R: Read + Seek + Send>(
+        &mut self,
+        py: Python,
+        source: &mut R,
+        vlr: &laz::LazVlr,
+        start: u64,
+        count: u64,
+        out: &mut [u8],
+        selection: laz::DecompressionSelection,
+        point_count: Option<u64>,
+    ) -> PyResult<()> {
+        let num_bytes = check_output_len(out.len(), count, vlr)?;
+
+        let data_start = self.data_start;
+        let pool = self.pool.clone();
+        let point_count = match point_count {
+            Some(point_count) => point_count,
+            None => self.point_count(source, vlr)?,
+        };
+        let chunk_table = self.chunk_table(source, vlr)?;
+        let chunks = covering_chunks(chunk_table.as_ref(), point_count, start, count)?;
+        py.detach(|| {
+            pool.install(|| {
+                read_range(
+                    source,
+                    data_start,
+                    vlr,
+                    chunk_table,
+                    &chunks,
+                    &mut out[..num_bytes],
+                    selection,
+                )
+            })
+        })
+        .map_err(into_py_err)
+    }
+}
@@ -0,0 +1,72 @@
+//! Progress reporting and cancellation of the long operations.
+//!
+//! Points are processed in batches of whole chunks. Between two batches,
+//! the `progress` callback is called and pending signals are handled,
+//! an exception raised by either (e.g. `KeyboardInterrupt`) stops the operation.
+use pyo3::prelude::*;
+
+use crate::errors::{into_py_err, IntoPyErr};
+
+/// Number of points of a batch when the chunks have a variable size
+const VARIABLE_SIZE_CHUNKS_BATCH_LEN: usize = 50_000;
+
+/// Returns the number of bytes of a batch of `num_chunks` chunks
+pub(crate) fn batch_size(vlr: &laz::LazVlr, num_chunks: usize) -> usize {
+    let chunk_size = if vlr.uses_variable_size_chunks() {
+        VARIABLE_SIZE_CHUNKS_BATCH_LEN
+    } else {
+        vlr.chunk_size() as usize
+    };
+    chunk_size * num_chunks.max(1) * vlr.items_size() as usize
+}
+
+/// Reports the progress to the optional `callable(points_done, total)`
+pub(crate) struct Progress {
+    callback: Option<Py<PyAny>>,
+    point_size: usize,
+    done: u64,
+    total: u64,
+}
+
+impl Progress {
+    pub(crate) fn new(callback: Option<Py<PyAny>>, vlr: &laz::LazVlr, total: u64) -> Self {
+        Self {
+            callback,
+            point_size: vlr.items_size() as usize,
+            done: 0,
+            total,
+        }
+    }
+
+    /// Records that the points of `num_bytes` bytes are done,
+    /// then handles the signals and calls the callback.
+    pub(crate) fn advance(&mut self, num_bytes: usize) -> PyResult<()> {
+        self.done += (num_bytes / self.point_size) as u64;
+        Python::attach(|py| {
+            py.check_signals()?;
+            if let Some(callback) = &self.callback {
+                callback.call1(py, (self.done, self.total))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Calls `process` on each batch, and reports the progress after each of them
+    pub(crate) fn run<B, E, F>(
+        &mut self,
+        batches: impl IntoIterator<Item = B>,
+        mut process: F,
+    ) -> PyResult<()>
+    where
+        B: AsRef<[u8]>,
+        E: IntoPyErr,
+        F: FnMut(B) -> Result<(), E>,
+    {
+        for batch in batches {
+            let num_bytes = batch.as_ref().len();
+            process(batch).map_err(into_py_err)?;
+            self.advance(num_bytes)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyType};
+
+use crate::adapters::Reader;
+use crate::chunk_iter::check_point_count;
+use crate::las::{LasHeader, LasMetadata, Vlr};
+use crate::selective::SelectiveReader;
+use crate::threads::Pool;
+use crate::{arrays, into_py_err, partial, DecompressionSelection, LazVlr, LazrsError};
+
+/// Where the points are decompressed from
+enum Points {
+    /// A stream, read by one of the laz decompressors
+    Stream(Box<dyn laz::LazDecompressor + Send + Sync>),
+    /// A memory-mapped file, the chunks are decompressed
+    /// in parallel, directly from the mapping
+    Mapped {
+        mmap: memmap2::Mmap,
+        chunk_table: laz::laszip::ChunkTable,
+        /// Position of the first chunk in the file
+        first_chunk: usize,
+        selection: laz::DecompressionSelection,
+    },
+}
+
+/// Reader of LAZ files.
+///
+/// Parses the header, VLRs and EVLRs of the file, then
+/// decompresses points using the LasZip VLR found.
+#[pyclass]
+pub(crate) struct LazReader {
+    metadata: LasMetadata,
+    laz_vlr: laz::LazVlr,
+    points: Points,
+    /// Index of the next point that will be read
+    current_point: u64,
+    pool: Pool,
+}
+
+/// Reads the metadata and the LasZip VLR, `source` must be at the start of the file
+pub(crate) fn read_metadata<R: Read + Seek>(
+    source: &mut R,
+) -> PyResult<(LasMetadata, laz::LazVlr)> {
+    let metadata = LasMetadata::read_from(source)?;
+    let laz_vlr = metadata
+        .laszip_vlr()
+        .ok_or_else(|| PyErr::new::<LazrsError, _>("The file does not have a LasZip VLR"))
+        .and_then(|vlr| laz::LazVlr::read_from(vlr.data.as_slice()).map_err(into_py_err))?;
+    Ok((metadata, laz_vlr))
+}
+
+impl LazReader {
+    fn from_reader(
+        mut source: Reader,
+        parallel: bool,
+        selection: Option<DecompressionSelection>,
+    ) -> PyResult<Self> {
+        source.seek(SeekFrom::Start(0))?;
+        let (metadata, laz_vlr) = read_metadata(&mut source)?;
+
+        let selection = selection.map_or_else(laz::DecompressionSelection::all, |s| s.0);
+        let source = SelectiveReader::wrap(source, &laz_vlr, selection)?;
+        let decompressor: Box<dyn laz::LazDecompressor + Send + Sync> = if parallel {
+            Box::new(
+                laz::ParLasZipDecompressor::selective(source, laz_vlr.clone(), selection)
+                    .map_err(into_py_err)?,
+            )
+        } else {
+            Box::new(
+                laz::LasZipDecompressor::selective(source, laz_vlr.clone(), selection)
+                    .map_err(into_py_err)?,
+            )
+        };
+
+        Ok(Self {
+            metadata,
+            laz_vlr,
+            points: Points::Stream(decompressor),
+            current_point: 0,
+            pool: Pool::default(),
+        })
+    }
+
+    fn from_mmap(mmap: memmap2::Mmap, selection: Option<DecompressionSelection>) -> PyResult<Self> {
+        let mut source = Cursor::new(&mmap[..]);
+        let (metadata, laz_vlr) = read_metadata(&mut source)?;
+        let chunk_table =
+            laz::laszip::ChunkTable::read_from(&mut source, &laz_vlr).map_err(into_py_err)?;
+        // The chunks start after the offset to the chunk table
+        let first_chunk =
+            metadata.header.offset_to_point_data as usize + std::mem::size_of::<i64>();
+        check_point_count(chunk_table.as_ref(), &laz_vlr, metadata.header.point_count)?;
+
+        Ok(Self {
+            metadata,
+            laz_vlr,
+            points: Points::Mapped {
+                mmap,
+                chunk_table,
+                first_chunk,
+                selection: selection.map_or_else(laz::DecompressionSelection::all, |s| s.0),
+            },
+            current_point: 0,
+            pool: Pool::default(),
+        })
+    }
+
+    /// Decompresses the points that follow the current point into `out`
+    fn decompress_into(&mut self, py: Python, out: &mut [u8]) -> PyResult<()> {
+        match &mut self.points {
+            Points::Stream(decompressor) => py
+                .detach(|| self.pool.install(|| decompressor.decompress_many(out)))
+                .map_err(into_py_err),
+            Points::Mapped {
+                mmap,
+                chunk_table,
+                first_chunk,
+                selection,
+            } => {
+                let count = out.len() as u64 / self.laz_vlr.items_size();
+                let chunks = partial::covering_chunks(
+                    chunk_table.as_ref(),
+                    self.metadata.header.point_count,
+                    self.current_point,
+                    count,
+                )?;
+                let compressed = mmap
+                    .get(*first_chunk + chunks.byte_offset as usize..)
+                    .ok_or_else(|| PyErr::new::<LazrsError, _>("The file is truncated"))?;
+                py.detach(|| {
+                    self.pool.install(|| {
+                        partial::par_decompress_range(
+                            compressed,
+                            &self.laz_vlr,
+                            &chunk_table.as_ref()[chunks.indices.clone()],
+                            chunks.skip,
+                            out,
+                            *selection,
+                        )
+                    })
+                })
+                .map_err(into_py_err)
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl LazReader {
+    #[new]
+    #[pyo3(signature = (source, parallel = false, selection = None))]
+    fn new(
+        py: Python,
+        source: Py<PyAny>,
+        parallel: bool,
+        selection: Option<DecompressionSelection>,
+    ) -> PyResult<Self> {
+        Self::from_reader(Reader::new(py, source)?, parallel, selection)
+    }
+
+    /// Opens the file at `path`.
+    ///
+    /// With `mmap=True`, the file is memory-mapped and the chunks are
+    /// decompressed in parallel directly from the mapping (`parallel` is ignored),
+    /// the file must have a chunk table and must not be modified while it is read.
+    #[classmethod]
+    #[pyo3(signature = (path, mmap = false, parallel = false, selection = None))]
+    fn open(
+        _cls: &Bound<'_, PyType>,
+        path: PathBuf,
+        mmap: bool,
+        parallel: bool,
+        selection: Option<DecompressionSelection>,
+    ) -> PyResult<Self> {
+        let file = File::open(path)?;
+        if mmap {
+            // SAFETY: the mapping is only valid as long as the file
+            // is not modified, which is documented as a requirement
+            let mmap = unsafe { memmap2::Mmap::map(&file)? };
+            Self::from_mmap(mmap, selection)
+        } else {
+            Self::from_reader(Reader::File(BufReader::new(file)), parallel, selection)
+        }
+    }
+
+    #[getter]
+    fn header(&self) -> LasHeader {
+        self.metadata.header.clone()
+    }
+
+    #[getter]
+    fn vlrs(&self) -> Vec<Vlr> {
+        self.metadata.vlrs.clone()
+    }
+
+    #[getter]
+    fn evlrs(&self) -> Vec<Vlr> {
+        self.metadata.evlrs.clone()
+    }
+
+    #[getter]
+    fn laz_vlr(&self) -> LazVlr {
+        LazVlr {
+            vlr: self.laz_vlr.clone(),
+        }
+    }
+
+    #[getter]
+    fn point_format_id(&self) -> u8 {
+        self.metadata.header.point_format_id
+    }
+
+    #[getter]
+    fn point_size(&self) -> u64 {
+        self.laz_vlr.items_size()
+    }
+
+    #[getter]
+    fn point_count(&self) -> u64 {
+        self.metadata.header.point_count
+    }
+
+    /// Reads (at most) the next `n` points, returns their bytes.
+    ///
+    /// Less than `n` points are returned when the end of the file is reached.
+    fn read_points<'py>(&mut self, py: Python<'py>, n: u64) -> PyResult<Bound<'py, PyBytes>> {
+        let n = std::cmp::min(n, self.point_count() - self.current_point);
+        let num_bytes = usize::try_from(n * self.laz_vlr.items_size())?;
+
+        let bytes = PyBytes::new_with(py, num_bytes, |output| self.decompress_into(py, output))?;
+        self.current_point += n;
+        Ok(bytes)
+    }
+
+    /// Like `read_points`, but returns a numpy structured array.
+    fn read_points_array<'py>(&mut self, py: Python<'py>, n: u64) -> PyResult<Bound<'py, PyAny>> {
+        let n = std::cmp::min(n, self.point_count() - self.current_point);
+        let laz_vlr = self.laz_vlr.clone();
+        let array = arrays::new_points_array(py, &laz_vlr, usize::try_from(n)?, |output| {
+            self.decompress_into(py, output)
+        })?;
+        self.current_point += n;
+        Ok(array)
+    }
+
+    /// Seeks to the point at the given index
+    fn seek(&mut self, point_idx: u64) -> PyResult<()> {
+        let point_idx = std::cmp::min(point_idx, self.point_count());
+        if let Points::Stream(decompressor) = &mut self.points {
+            decompressor.seek(point_idx).map_err(into_py_err)?;
+        }
+        self.current_point = point_idx;
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+//! Recovery of the points of LAZ data whose chunk table is missing or corrupt.
+//!
+//! The chunks are decompressed one after the other, from the start of the
+//! point data, the chunk table is rebuilt from the position where each chunk ends.
+use std::ffi::CString;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use laz::laszip::{ChunkTableEntry, CompressorType};
+use laz::record::{
+    LayeredPointRecordDecompressor, RecordDecompressor, SequentialPointRecordDecompressor,
+};
+use pyo3::exceptions::PyUserWarning;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::adapters::{Reader, Writer};
+use crate::vlr::VlrHeader;
+use crate::{into_py_err, ChunkTable, LazVlr, LazrsError};
+
+/// Chunk by chunk decompression of point data that has no usable chunk table
+struct ChunkRecovery<'a> {
+    vlr: &'a laz::LazVlr,
+    record_decompressor: Box<dyn RecordDecompressor<Cursor<&'a [u8]>> + Send + Sync + 'a>,
+    layered: bool,
+    /// Number of points left to recover, if known
+    points_left: Option<u64>,
+    /// Number of chunks recovered
+    num_chunks: u32,
+    /// End of the chunks, the end of the data unless a chunk table is found before it
+    data_end: u64,
+    /// Whether a point-wise chunk ended where the data ends,
+    /// the data that follows it cannot be trusted
+    ended: bool,
+}
+
+impl<'a> ChunkRecovery<'a> {
+    /// `chunks` is the data after the offset to the chunk table
+    fn new(chunks: &'a [u8], vlr: &'a laz::LazVlr, point_count: Option<u64>) -> PyResult<Self> {
+        let compressor = VlrHeader::of(vlr)?.compressor;
+        let layered = compressor == CompressorType::LayeredChunked as u16;
+        if compressor != CompressorType::PointWiseChunked as u16 && !layered {
+            return Err(PyErr::new::<LazrsError, _>(
+                "The points are not compressed in chunks, there is no chunk table to recover",
+            ));
+        }
+        if !layered && vlr.uses_variable_size_chunks() {
+            return Err(PyErr::new::<LazrsError, _>(
+                "The ends of variable-size chunks can only be found in layered (point format >= 6) data",
+            ));
+        }
+        let record_decompressor: Box<dyn RecordDecompressor<_> + Send + Sync> = if layered {
+            Box::new(LayeredPointRecordDecompressor::new(Cursor::new(chunks)))
+        } else {
+            Box::new(SequentialPointRecordDecompressor::new(Cursor::new(chunks)))
+        };
+        Ok(Self {
+            vlr,
+            record_decompressor,
+            layered,
+            points_left: point_count,
+            num_chunks: 0,
+            data_end: chunks.len() as u64,
+            ended: false,
+        })
+    }
+
+    /// Whether the header of a chunk table of the chunks recovered so far
+    /// (and the current one) is at `position`
+    fn chunk_table_at(&self, position: u64) -> bool {
+        let data = self.record_decompressor.get().get_ref();
+        let mut header = [0u8; 8];
+        header[4..].copy_from_slice(&(self.num_chunks + 1).to_le_bytes());
+        data.get(position as usize..)
+            .is_some_and(|rest| rest.starts_with(&header))
+    }
+
+    /// Decompresses the next chunk, returns its entry in the chunk table,
+    /// `None` when the data ends or the chunk cannot be decompressed.
+    fn next_chunk(&mut self, points: &mut Vec<u8>) -> Option<ChunkTableEntry> {
+        let start = self.record_decompressor.get().position();
+        if self.ended || start >= self.data_end || self.points_left == Some(0) {
+            return None;
+        }
+        self.record_decompressor.reset();
+        self.record_decompressor
+            .set_fields_from(self.vlr.items())
+            .ok()?;
+
+        let point_size = self.vlr.items_size() as usize;
+        let point_count = if self.layered {
+            // Layered chunks store their number of points after their first point
+            let data = self.record_decompressor.get().get_ref();
+            let count_start = start as usize + point_size;
+            let count = data.get(count_start..count_start + 4)?;
+            u64::from(u32::from_le_bytes(count.try_into().unwrap()))
+        } else {
+            u64::from(self.vlr.chunk_size())
+        };
+        let chunk_start = points.len();
+        let mut num_points = 0u64;
+        let mut end = start;
+        let num_points_max = point_count.min(self.points_left.unwrap_or(u64::MAX));
+        while num_points < num_points_max {
+            points.resize(points.len() + point_size, 0);
+            let point = &mut points[chunk_start + num_points as usize * point_size..];
+            let decompressed = self.record_decompressor.decompress_next(point);
+            let position = self.record_decompressor.get().position();
+            if decompressed.is_err() || position > self.data_end {
+                if self.layered || num_points == 0 {
+                    // The chunk is truncated, its points cannot be trusted
+                    points.truncate(chunk_start);
+                    return None;
+                }
+                // The points of a point-wise chunk are decompressed one after the other,
+                // the ones decompressed within the data are kept
+                points.truncate(chunk_start + num_points as usize * point_size);
+                self.ended = true;
+                break;
+            }
+            num_points += 1;
+            end = position;
+            if !self.layered && self.chunk_table_at(end) {
+                // The data goes on with the chunk table (and the EVLRs),
+                // the last points may still be decompressed without reading more data
+                self.data_end = end;
+            }
+            if !self.layered && end == self.data_end && num_points < num_points_max {
+                // The next points may be decompressed without reading more data,
+                // whether they are real ones cannot be known
+                self.ended = true;
+            }
+        }
+
+        if let Some(points_left) = &mut self.points_left {
+            *points_left -= num_points;
+        }
+        self.num_chunks += 1;
+        Some(ChunkTableEntry {
+            point_count: num_points,
+            byte_count: end - start,
+        })
+    }
+}
+
+/// Returns the number of points of the chunks of the table.
+///
+/// With fixed size chunks, the last entry of the table says the chunk is full.
+/// Layered chunks (point formats >= 6) store their number of points, so it is
+/// read from the last chunk, but point-wise chunks do not, their number of points
+/// has to be given (it is the point count of the LAS header).
+/// `first_chunk` is the position of the first chunk in `source`.
+pub(crate) fn table_point_count<R: Read + Seek>(
+    source: &mut R,
+    first_chunk: u64,
+    entries: &[ChunkTableEntry],
+    vlr: &laz::LazVlr,
+) -> PyResult<u64> {
+    match entries.split_last() {
+        Some((_, others)) if !vlr.uses_variable_size_chunks() => {
+            if VlrHeader::of(vlr)?.compressor != CompressorType::LayeredChunked as u16 {
+                return Err(PyErr::new::<LazrsError, _>(
+                    "The number of points of the last chunk is only stored for the point formats >= 6, \
+                    point_count must be given",
+                ));
+            }
+            // Layered chunks store their number of points after their first point
+            let last_chunk_start = others.iter().map(|entry| entry.byte_count).sum::<u64>();
+            source.seek(SeekFrom::Start(
+                first_chunk + last_chunk_start + vlr.items_size(),
+            ))?;
+            let num_points = u64::from(source.read_u32::<LittleEndian>()?);
+            let chunk_size = u64::from(vlr.chunk_size());
+            if num_points > chunk_size {
+                return Err(PyErr::new::<LazrsError, _>(format!(
+                    "The last chunk has {} points, more than the chunk size ({})",
+                    num_points, chunk_size
+                )));
+            }
+            Ok(others.len() as u64 * chunk_size + num_points)
+        }
+        _ => Ok(entries.iter().map(|entry| entry.point_count).sum()),
+    }
+}
+
+/// Reads the point data of `source`, which must be at the start of the point data
+/// (at the offset to the chunk table).
+///
+/// The chunks end at the offset to the chunk table if it looks valid,
+/// otherwise at the end of the source.
+fn read_point_data(source: &mut Reader) -> PyResult<Vec<u8>> {
+    let data_start = source.stream_position()?;
+    let offset = source.read_i64::<LittleEndian>()?;
+    let first_chunk = source.stream_position()?;
+    let source_end = source.seek(SeekFrom::End(0))?;
+    let end = if offset > first_chunk as i64 && offset as u64 <= source_end {
+        offset as u64
+    } else {
+        source_end
+    };
+    let mut chunks = vec![0u8; (end - first_chunk) as usize];
+    source.seek(SeekFrom::Start(first_chunk))?;
+    source.read_exact(&mut chunks)?;
+    source.seek(SeekFrom::Start(data_start))?;
+    Ok(chunks)
+}
+
+/// Decompresses all the chunks that can be recovered, returns their points and entries,
+/// and whether the last chunk is a point-wise chunk that ended where the data ends
+fn recover(
+    chunks: &[u8],
+    vlr: &laz::LazVlr,
+    point_count: Option<u64>,
+) -> PyResult<(Vec<u8>, laz::laszip::ChunkTable, bool)> {
+    let mut recovery = ChunkRecovery::new(chunks, vlr, point_count)?;
+    let mut points = Vec::new();
+    let mut chunk_table = laz::laszip::ChunkTable::default();
+    while let Some(entry) = recovery.next_chunk(&mut points) {
+        chunk_table.push(entry);
+    }
+    Ok((points, chunk_table, recovery.ended))
+}
+
+/// Warns that the number of points of the last chunk was not known,
+/// its points were decompressed until the data ended
+fn warn_last_chunk(py: Python, chunk_table: &laz::laszip::ChunkTable) -> PyResult<()> {
+    let num_points = chunk_table
+        .as_ref()
+        .last()
+        .map_or(0, |entry| entry.point_count);
+    let message = format!(
+        "Point-wise chunks do not store their number of points, the {} points of the last chunk \
+        were decompressed until the data ended and the last ones may not be real points, \
+        point_count should be given",
+        num_points
+    );
+    PyErr::warn(
+        py,
+        &py.get_type::<PyUserWarning>(),
+        &CString::new(message).unwrap(),
+        1,
+    )
+}
+
+/// Recovers the points of LAZ data whose chunk table is missing or corrupt,
+/// for example when the writer was interrupted.
+///
+/// `source` is a path or a file object positioned at the start of the points data.
+///
+/// The chunks are decompressed one after the other, until `point_count` points
+/// are recovered, or the data ends, or a chunk cannot be decompressed
+/// (such as a truncated last chunk). The data ends at the offset to the chunk
+/// table when it is valid, at the end of the source otherwise.
+///
+/// Point-wise chunks (point formats < 6) do not store their number of points,
+/// without `point_count` one ends at the chunk size, where a point cannot be
+/// decompressed or where a chunk table starts (with the offset to it lost).
+/// The decompressor may reach the end of the data a few points before the real
+/// end of the chunk, and decompress a few more points that do not exist after it,
+/// so the points are decompressed until the data ended and a `UserWarning` is issued
+/// as the last points may not be real ones: `point_count` (the point count of the
+/// LAS header) should be given when it is known.
+///
+/// Variable-size chunks can only be recovered for the point formats >= 6,
+/// which store the number of points of each chunk.
+///
+/// Returns the points and the rebuilt chunk table.
+#[pyfunction]
+#[pyo3(signature = (source, vlr, point_count = None))]
+pub(crate) fn recover_points<'py>(
+    py: Python<'py>,
+    source: Py<PyAny>,
+    vlr: &LazVlr,
+    point_count: Option<u64>,
+) -> PyResult<(Bound<'py, PyBytes>, ChunkTable)> {
+    let mut source = Reader::new(py, source)?;
+    let chunks = read_point_data(&mut source)?;
+    let (points, chunk_table, ended) = py.detach(|| recover(&chunks, &vlr.vlr, point_count))?;
+    if ended && point_count.is_none() {
+        warn_last_chunk(py, &chunk_table)?;
+    }
+    Ok((PyBytes::new(py, &points), ChunkTable::from(chunk_table)))
+}
+
+/// Writes to `dest` a repaired copy of the point data of `source`,
+/// the chunks that could be recovered followed by a rebuilt chunk table.
+///
+/// The chunks are recovered as with `recover_points`,
+/// `dest` is a path or a file object positioned where the point data is to be written.
+///
+/// Returns the rebuilt chunk table, the number of points of the LAS header
+/// has to be updated if some of them were not recovered.
+#[pyfunction]
+#[pyo3(signature = (source, dest, vlr, point_count = None))]
+pub(crate) fn repair_point_data(
+    py: Python,
+    source: Py<PyAny>,
+    dest: Py<PyAny>,
+    vlr: &LazVlr,
+    point_count: Option<u64>,
+) -> PyResult<ChunkTable> {
+    let mut source = Reader::new(py, source)?;
+    let chunks = read_point_data(&mut source)?;
+    let (_, chunk_table, ended) = py.detach(|| recover(&chunks, &vlr.vlr, point_count))?;
+    if ended && point_count.is_none() {
+        warn_last_chunk(py, &chunk_table)?;
+    }
+
+    let num_bytes = chunk_table
+        .as_ref()
+        .iter()
+        .map(|entry| entry.byte_count)
+        .sum::<u64>();
+    let mut dest = Writer::new(py, dest)?;
+    let data_start = dest.stream_position()?;
+    let offset = data_start + std::mem::size_of::<i64>() as u64 + num_bytes;
+    dest.write_i64::<LittleEndian>(offset as i64)?;
+    dest.write_all(&chunks[..num_bytes as usize])?;
+    chunk_table
+        .write_to(&mut dest, &vlr.vlr)
+        .map_err(into_py_err)?;
+    dest.flush()?;
+    Ok(ChunkTable::from(chunk_table))
+}
@@ -0,0 +1,70 @@
+//! Restoration of the destination of a compressor or an appender,
+//! for when an error happens before `done()` is called.
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::adapters::Truncate;
+
+/// The original content of the parts of the destination
+/// that the compressor or appender overwrites
+pub(crate) struct Rollback {
+    /// The `(position, data)` of the overwritten parts
+    saved: Vec<(u64, Vec<u8>)>,
+    /// The original length of the destination
+    len: u64,
+}
+
+impl Rollback {
+    /// For a compressor, which writes from the current position of `dest`
+    pub(crate) fn compressor<W: Seek>(dest: &mut W) -> std::io::Result<Self> {
+        Ok(Self {
+            saved: Vec::new(),
+            len: dest.stream_position()?,
+        })
+    }
+
+    /// For an appender, `data` must be positioned at the start of the point data.
+    ///
+    /// The appender overwrites the offset to the chunk table, and re-compresses
+    /// the chunk that holds the last point, so the data is overwritten from
+    /// the start of that chunk.
+    pub(crate) fn appender<R: Read + Seek>(
+        data: &mut R,
+        vlr: &laz::LazVlr,
+        point_count: u64,
+    ) -> laz::Result<Self> {
+        let start = data.stream_position()?;
+        let mut offset = vec![0u8; std::mem::size_of::<i64>()];
+        data.read_exact(&mut offset)?;
+        data.seek(SeekFrom::Start(start))?;
+        let chunk_table = laz::laszip::ChunkTable::read_from(&mut *data, vlr)?;
+        let entries = chunk_table.as_ref();
+
+        let mut position = start + offset.len() as u64;
+        let mut num_points = 0u64;
+        for entry in entries.iter().take(entries.len().saturating_sub(1)) {
+            if num_points + entry.point_count >= point_count {
+                break;
+            }
+            num_points += entry.point_count;
+            position += entry.byte_count;
+        }
+
+        let mut tail = Vec::new();
+        data.seek(SeekFrom::Start(position))?;
+        data.read_to_end(&mut tail)?;
+        data.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            len: position + tail.len() as u64,
+            saved: vec![(start, offset), (position, tail)],
+        })
+    }
+
+    /// Puts back the original data, and removes what was written after it
+    pub(crate) fn apply<W: Write + Seek + Truncate>(&self, dest: &mut W) -> std::io::Result<()> {
+        for (position, data) in &self.saved {
+            dest.seek(SeekFrom::Start(*position))?;
+            dest.write_all(data)?;
+        }
+        dest.truncate(self.len)
+    }
+}
@@ -0,0 +1,274 @@
+//! Reading of only the selected layers of the point formats 6 to 10.
+//!
+//! A layered chunk starts with its first point, its number of points and the
+//! size of each of its layers, followed by the layers. The decompressors seek over
+//! the layers that are not selected, but a buffered source would still read them,
+//! so the chunks are loaded here with only the bytes of the selected layers read
+//! from the source, the other bytes are zeros that the decompressors never look at.
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use laz::laszip::CompressorType;
+use laz::{DecompressionSelection, LazItemType};
+use pyo3::prelude::*;
+
+use crate::adapters::Reader;
+use crate::vlr::VlrHeader;
+
+/// Size of the reads outside of the chunks (the offset and the chunk table)
+const GAP_READ_SIZE: usize = 8192;
+
+/// Bytes read from the source by a decompressor created with a selection
+#[pyclass(frozen, skip_from_py_object)]
+#[derive(Copy, Clone, Default)]
+pub(crate) struct ReadStats {
+    /// Number of bytes read from the source
+    #[pyo3(get)]
+    bytes_read: u64,
+    /// Number of bytes of the layers that were not selected, which were not read
+    #[pyo3(get)]
+    bytes_skipped: u64,
+}
+
+#[pymethods]
+impl ReadStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "<ReadStats(bytes_read: {}, bytes_skipped: {})>",
+            self.bytes_read, self.bytes_skipped
+        )
+    }
+}
+
+/// Whether each layer of a chunk is selected, in the order they are stored
+fn selected_layers(vlr: &laz::LazVlr, selection: DecompressionSelection) -> Vec<bool> {
+    let mut layers = Vec::new();
+    for item in vlr.items() {
+        match item.item_type() {
+            LazItemType::Point14 => layers.extend([
+                true,
+                selection.should_decompress_z(),
+                selection.should_decompress_classification(),
+                selection.should_decompress_flags(),
+                selection.should_decompress_intensity(),
+                selection.should_decompress_scan_angle(),
+                selection.should_decompress_user_data(),
+                selection.should_decompress_point_source_id(),
+                selection.should_decompress_gps_time(),
+            ]),
+            LazItemType::RGB14 => layers.push(selection.should_decompress_rgb()),
+            LazItemType::RGBNIR14 => layers.extend([
+                selection.should_decompress_rgb(),
+                selection.should_decompress_nir(),
+            ]),
+            LazItemType::WavePacket14 => layers.push(selection.should_decompress_wavepacket()),
+            LazItemType::Byte14(count) => layers.extend(std::iter::repeat_n(
+                selection.should_decompress_extra_bytes(),
+                usize::from(count),
+            )),
+            _ => layers.push(true),
+        }
+    }
+    layers
+}
+
+/// Part of the source held in memory
+struct Loaded {
+    start: u64,
+    data: Vec<u8>,
+}
+
+impl Loaded {
+    fn end(&self) -> u64 {
+        self.start + self.data.len() as u64
+    }
+}
+
+/// Source of layered chunks that only reads the selected layers
+pub(crate) struct SelectiveReader {
+    source: Reader,
+    /// Position of each chunk in the source
+    chunks: Vec<Range<u64>>,
+    layers: Vec<bool>,
+    point_size: usize,
+    position: u64,
+    loaded: Option<Loaded>,
+    stats: ReadStats,
+}
+
+impl SelectiveReader {
+    /// Returns the source wrapped in a `SelectiveReader` when some layers can be skipped,
+    /// that is when the points are layered, not all their layers are selected,
+    /// and the source has a chunk table. Otherwise, the source is returned as is.
+    ///
+    /// The source must be at the start of the point data, where it is left.
+    pub(crate) fn wrap(
+        mut source: Reader,
+        vlr: &laz::LazVlr,
+        selection: DecompressionSelection,
+    ) -> PyResult<Reader> {
+        let layers = selected_layers(vlr, selection);
+        if VlrHeader::of(vlr)?.compressor != CompressorType::LayeredChunked as u16
+            || layers.iter().all(|selected| *selected)
+        {
+            return Ok(source);
+        }
+        let data_start = source.stream_position()?;
+        let chunk_table = laz::laszip::ChunkTable::read_from(&mut source, vlr);
+        source.seek(SeekFrom::Start(data_start))?;
+        let Ok(chunk_table) = chunk_table else {
+            // Without the chunk table, the chunks are only found while decompressing
+            return Ok(source);
+        };
+
+        let mut start = data_start + std::mem::size_of::<i64>() as u64;
+        let chunks = chunk_table
+            .as_ref()
+            .iter()
+            .map(|entry| {
+                let chunk = start..start + entry.byte_count;
+                start = chunk.end;
+                chunk
+            })
+            .collect();
+        Ok(Reader::Selective(Box::new(Self {
+            source,
+            chunks,
+            layers,
+            point_size: vlr.items_size() as usize,
+            position: data_start,
+            loaded: None,
+            stats: ReadStats::default(),
+        })))
+    }
+
+    pub(crate) fn stats(&self) -> ReadStats {
+        self.stats
+    }
+
+    /// Reads into `buf` from `position`, without reading ahead
+    fn read_source(&mut self, position: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        let num_read = self.source.read_unbuffered_at(position, buf)?;
+        self.stats.bytes_read += num_read as u64;
+        Ok(num_read)
+    }
+
+    fn read_exact_source(&mut self, position: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        if self.read_source(position, buf)? != buf.len() {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
+    /// Reads the bytes at the current position as they are in the source,
+    /// with the layers that are not selected
+    pub(crate) fn read_exact_raw(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.read_exact_source(self.position, buf)?;
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Loads the chunk, only reading its selected layers
+    fn load_chunk(&mut self, chunk: Range<u64>) -> std::io::Result<Loaded> {
+        let mut data = vec![0u8; (chunk.end - chunk.start) as usize];
+        let header_size = self.point_size + 4 + 4 * self.layers.len();
+        let mut reads: Vec<Range<usize>> = Vec::new();
+        let mut offset = 0;
+        if data.len() >= header_size {
+            self.read_exact_source(chunk.start, &mut data[..header_size])?;
+            offset = header_size;
+            let layer_sizes = data[self.point_size + 4..header_size]
+                .chunks_exact(4)
+                .map(|size| u32::from_le_bytes(size.try_into().unwrap()) as usize)
+                .collect::<Vec<_>>();
+            // When the sizes do not add up, the chunk is corrupt, it is read whole
+            // so that the decompressor reports it
+            if header_size + layer_sizes.iter().sum::<usize>() == data.len() {
+                for (size, selected) in layer_sizes.into_iter().zip(&self.layers) {
+                    let layer = offset..offset + size;
+                    offset = layer.end;
+                    match reads.last_mut() {
+                        _ if !selected => self.stats.bytes_skipped += size as u64,
+                        Some(read) if read.end == layer.start => read.end = layer.end,
+                        _ => reads.push(layer),
+                    }
+                }
+            }
+        }
+        if offset < data.len() {
+            reads.push(offset..data.len());
+        }
+        for read in reads {
+            self.read_exact_source(chunk.start + read.start as u64, &mut data[read])?;
+        }
+        Ok(Loaded {
+            start: chunk.start,
+            data,
+        })
+    }
+
+    /// Loads what is at the current position
+    fn load(&mut self) -> std::io::Result<Loaded> {
+        let position = self.position;
+        let next_chunk = self.chunks.partition_point(|chunk| chunk.end <= position);
+        match self.chunks.get(next_chunk) {
+            Some(chunk) if chunk.start <= position => self.load_chunk(chunk.clone()),
+            next_chunk => {
+                let len = next_chunk.map_or(GAP_READ_SIZE, |chunk| {
+                    GAP_READ_SIZE.min((chunk.start - position) as usize)
+                });
+                let mut data = vec![0u8; len];
+                let num_read = self.read_source(position, &mut data)?;
+                data.truncate(num_read);
+                Ok(Loaded {
+                    start: position,
+                    data,
+                })
+            }
+        }
+    }
+}
+
+impl Read for SelectiveReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let position = self.position;
+        let loaded = match self.loaded.take() {
+            Some(loaded) if (loaded.start..loaded.end()).contains(&position) => loaded,
+            _ => self.load()?,
+        };
+        let available = loaded
+            .data
+            .get((position - loaded.start) as usize..)
+            .unwrap_or_default();
+        let num_read = buf.len().min(available.len());
+        buf[..num_read].copy_from_slice(&available[..num_read]);
+        self.position += num_read as u64;
+        self.loaded = Some(loaded);
+        Ok(num_read)
+    }
+}
+
+impl Seek for SelectiveReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            SeekFrom::End(_) => Some(self.source.seek(pos)?),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// Returns the stats of the source, `None` if it does not skip layers
+pub(crate) fn read_stats(source: &Reader) -> Option<ReadStats> {
+    match source {
+        Reader::Selective(reader) => Some(reader.stats()),
+        _ => None,
+    }
+}
@@ -0,0 +1,126 @@
+//! Thread pools used for the parallel compression and decompression.
+//!
+//! By default, rayon's global pool is used, `set_num_threads` replaces the
+//! default by a dedicated pool, and the functions & classes that work in
+//! parallel accept `num_threads` or `thread_pool` to use another pool.
+use std::sync::{Arc, RwLock};
+
+use pyo3::prelude::*;
+
+use crate::LazrsError;
+
+/// The default pool, `None` means rayon's global pool
+static DEFAULT_POOL: RwLock<Option<Arc<rayon::ThreadPool>>> = RwLock::new(None);
+
+fn build_pool(num_threads: usize) -> PyResult<Arc<rayon::ThreadPool>> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .thread_name(|index| format!("lazrs-{}", index))
+        .build()
+        .map(Arc::new)
+        .map_err(|e| PyErr::new::<LazrsError, _>(e.to_string()))
+}
+
+/// A pool of threads that can be shared by the parallel functions & classes
+#[pyclass(frozen)]
+pub(crate) struct ThreadPool {
+    pool: Arc<rayon::ThreadPool>,
+}
+
+#[pymethods]
+impl ThreadPool {
+    #[new]
+    fn new(num_threads: usize) -> PyResult<Self> {
+        if num_threads == 0 {
+            return Err(PyErr::new::<LazrsError, _>(
+                "The number of threads must be at least 1",
+            ));
+        }
+        Ok(Self {
+            pool: build_pool(num_threads)?,
+        })
+    }
+
+    #[getter]
+    fn num_threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<ThreadPool(num_threads: {})>", self.num_threads())
+    }
+}
+
+/// Sets the number of threads used by default for parallel work.
+///
+/// 0 means using rayon's global pool, which is the default.
+#[pyfunction]
+pub(crate) fn set_num_threads(num_threads: usize) -> PyResult<()> {
+    let pool = if num_threads == 0 {
+        None
+    } else {
+        Some(build_pool(num_threads)?)
+    };
+    *DEFAULT_POOL.write().unwrap() = pool;
+    Ok(())
+}
+
+/// Returns the number of threads used by default for parallel work
+#[pyfunction]
+pub(crate) fn get_num_threads() -> usize {
+    Pool::default().install(rayon::current_num_threads)
+}
+
+/// The pool on which some parallel work runs
+#[derive(Clone)]
+pub(crate) struct Pool(Option<Arc<rayon::ThreadPool>>);
+
+impl Default for Pool {
+    fn default() -> Self {
+        Self(DEFAULT_POOL.read().unwrap().clone())
+    }
+}
+
+impl Pool {
+    /// Gets the pool from the `num_threads` and `thread_pool` arguments,
+    /// uses the default pool if none of them is given.
+    pub(crate) fn from_args(
+        num_threads: Option<usize>,
+        thread_pool: Option<&ThreadPool>,
+    ) -> PyResult<Self> {
+        match (num_threads, thread_pool) {
+            (Some(_), Some(_)) => Err(PyErr::new::<LazrsError, _>(
+                "num_threads and thread_pool cannot be both given",
+            )),
+            (Some(0), None) => Err(PyErr::new::<LazrsError, _>(
+                "The number of threads must be at least 1",
+            )),
+            (Some(num_threads), None) => build_pool(num_threads).map(|pool| Self(Some(pool))),
+            (None, Some(thread_pool)) => Ok(Self(Some(Arc::clone(&thread_pool.pool)))),
+            (None, None) => Ok(Self::default()),
+        }
+    }
+
+    /// Runs `f` in the pool, so that rayon's parallel iterators use its threads
+    pub(crate) fn install<R, F>(&self, f: F) -> R
+    where
+        R: Send,
+        F: FnOnce() -> R + Send,
+    {
+        match &self.0 {
+            Some(pool) => pool.install(f),
+            None => f(),
+        }
+    }
+
+    /// Runs `f` in the background, on one of the pool's threads
+    pub(crate) fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        match &self.0 {
+            Some(pool) => pool.spawn(f),
+            None => rayon::spawn(f),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Transcoding of LAZ points to another point format of the same family
+//! (e.g. 3 -> 1, or 8 -> 7), dropping layers such as RGB or the extra bytes.
+use std::io::{Seek, SeekFrom, Write};
+
+use laz::laszip::{ChunkTableEntry, LazItemType};
+use laz::DecompressionSelection;
+use pyo3::prelude::*;
+
+use crate::adapters::{Reader, Writer};
+use crate::chunk_iter::trimmed_entries;
+use crate::progress::Progress;
+use crate::recovery::table_point_count;
+use crate::threads::{Pool, ThreadPool};
+use crate::vlr::item_type_name;
+use crate::{into_py_err, LazVlr, LazrsError};
+
+/// Copy of the bytes of an item, from the source record to the destination record
+struct ItemCopy {
+    src_offset: usize,
+    dst_offset: usize,
+    len: usize,
+}
+
+fn item_offsets(items: &[laz::LazItem]) -> Vec<usize> {
+    items
+        .iter()
+        .scan(0usize, |offset, item| {
+            let start = *offset;
+            *offset += usize::from(item.size());
+            Some(start)
+        })
+        .collect()
+}
+
+/// Where each destination item comes from in the source records
+fn record_layout(
+    src_items: &[laz::LazItem],
+    dst_items: &[laz::LazItem],
+    dst_point_format: u8,
+) -> PyResult<Vec<ItemCopy>> {
+    let src_offsets = item_offsets(src_items);
+    let dst_offsets = item_offsets(dst_items);
+    dst_items
+        .iter()
+        .zip(dst_offsets)
+        .map(|(dst_item, dst_offset)| {
+            src_items
+                .iter()
+                .position(|src_item| {
+                    src_item.item_type() == dst_item.item_type()
+                        // The RGB are the first bytes of RGBNIR
+                        || (dst_item.item_type() == LazItemType::RGB14
+                            && src_item.item_type() == LazItemType::RGBNIR14)
+                })
+                .map(|index| ItemCopy {
+                    src_offset: src_offsets[index],
+                    dst_offset,
+                    len: usize::from(dst_item.size()),
+                })
+                .ok_or_else(|| {
+                    PyErr::new::<LazrsError, _>(format!(
+                        "The source points have no {} item, they cannot be transcoded to point format {}",
+                        item_type_name(dst_item.item_type().into()),
+                        dst_point_format
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Only the layers of the destination items are decompressed
+/// (which only matters for the layered point formats)
+fn selection(dst_items: &[laz::LazItem]) -> DecompressionSelection {
+    let has = |item_type: LazItemType| dst_items.iter().any(|item| item.item_type() == item_type);
+    let mut selection = DecompressionSelection::ALL;
+    if !has(LazItemType::RGB14) && !has(LazItemType::RGBNIR14) {
+        selection &= !DecompressionSelection::RGB;
+    }
+    if !has(LazItemType::RGBNIR14) {
+        selection &= !DecompressionSelection::NIR;
+    }
+    if !has(LazItemType::WavePacket14) {
+        selection &= !DecompressionSelection::WAVEPACKET;
+    }
+    if !dst_items
+        .iter()
+        .any(|item| matches!(item.item_type(), LazItemType::Byte14(_)))
+    {
+        selection &= !DecompressionSelection::ALL_EXTRA_BYTES;
+    }
+    DecompressionSelection(selection)
+}
+
+/// Returns the entries of the chunks of the source, positions it at the start of the point data
+fn source_entries(
+    source: &mut Reader,
+    vlr: &laz::LazVlr,
+    point_count: Option<u64>,
+) -> PyResult<Vec<ChunkTableEntry>> {
+    let data_start = source.stream_position()?;
+    let chunk_table = laz::laszip::ChunkTable::read_from(&mut *source, vlr).map_err(into_py_err)?;
+    let entries = chunk_table.as_ref();
+
+    let point_count = match point_count {
+        Some(point_count) => point_count,
+        None => {
+            let first_chunk = data_start + std::mem::size_of::<i64>() as u64;
+            table_point_count(source, first_chunk, entries, vlr)?
+        }
+    };
+    source.seek(SeekFrom::Start(data_start))?;
+    Ok(trimmed_entries(entries, point_count))
+}
+
+/// Transcodes the compressed points of `src` to the point format `dst_point_format`,
+/// and writes them, compressed, to `dst`.
+///
+/// `src` is a path or a file object positioned at the start of the points data,
+/// `dst` is a path or a file object positioned where the points are to be written.
+///
+/// The destination point format must be of the same family as the source
+/// (0 to 5, or 6 to 10), and its fields must be in the source, the fields that are not
+/// (such as RGB, NIR or the wave packet) are dropped, as are the extra bytes
+/// when `drop_extra_bytes` is true.
+///
+/// `point_count` is the number of points to transcode, all of them by default.
+/// With fixed size chunks, the number of points of the last chunk is only stored
+/// for the point formats >= 6, for the other formats `point_count`
+/// (the point count of the LAS header) must be given.
+///
+/// Returns the LazVlr of the destination, it uses the chunk size of the source.
+#[pyfunction]
+#[pyo3(signature = (
+    src,
+    dst,
+    src_vlr,
+    dst_point_format,
+    drop_extra_bytes = false,
+    point_count = None,
+    progress = None,
+    num_threads = None,
+    thread_pool = None
+))]
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn transcode<'py>(
+    py: Python<'py>,
+    src: Py<PyAny>,
+    dst: Py<PyAny>,
+    src_vlr: &LazVlr,
+    dst_point_format: u8,
+    drop_extra_bytes: bool,
+    point_count: Option<u64>,
+    progress: Option<Py<PyAny>>,
+    num_threads: Option<usize>,
+    thread_pool: Option<PyRef<'py, ThreadPool>>,
+) -> PyResult<LazVlr> {
+    let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
+    let src_vlr = &src_vlr.vlr;
+    let src_items = src_vlr.items();
+    let num_extra_bytes = if drop_extra_bytes {
+        0
+    } else {
+        src_items
+            .iter()
+            .filter(|item| {
+                matches!(
+                    item.item_type(),
+                    LazItemType::Byte(_) | LazItemType::Byte14(_)
+                )
+            })
+            .map(laz::LazItem::size)
+            .sum()
+    };
+    let mut builder = laz::LazVlrBuilder::default()
+        .with_point_format(dst_point_format, num_extra_bytes)
+        .map_err(into_py_err)?;
+    builder = if src_vlr.uses_variable_size_chunks() {
+        builder.with_variable_chunk_size()
+    } else {
+        builder.with_fixed_chunk_size(src_vlr.chunk_size())
+    };
+    let dst_vlr = builder.build();
+    let layout = record_layout(src_items, dst_vlr.items(), dst_point_format)?;
+
+    let mut source = Reader::new(py, src)?;
+    let entries = source_entries(&mut source, src_vlr, point_count)?;
+    let mut decompressor =
+        laz::LasZipDecompressor::selective(source, src_vlr.clone(), selection(dst_vlr.items()))
+            .map_err(into_py_err)?;
+    let mut compressor = laz::ParLasZipCompressor::new(Writer::new(py, dst)?, dst_vlr.clone())
+        .map_err(into_py_err)?;
+
+    let src_point_size = src_vlr.items_size() as usize;
+    let dst_point_size = dst_vlr.items_size() as usize;
+    let total = entries.iter().map(|entry| entry.point_count).sum();
+    let mut progress = Progress::new(progress, src_vlr, total);
+    let batch_len = pool.install(rayon::current_num_threads).max(1);
+    py.detach(|| -> PyResult<()> {
+        compressor
+            .reserve_offset_to_chunk_table()
+            .map_err(into_py_err)?;
+        for batch in entries.chunks(batch_len) {
+            let num_points = batch.iter().map(|entry| entry.point_count).sum::<u64>() as usize;
+            let mut src_points = vec![0u8; num_points * src_point_size];
+            decompressor
+                .decompress_many(&mut src_points)
+                .map_err(into_py_err)?;
+
+            let mut dst_points = vec![0u8; num_points * dst_point_size];
+            for (src_point, dst_point) in src_points
+                .chunks_exact(src_point_size)
+                .zip(dst_points.chunks_exact_mut(dst_point_size))
+            {
+                for copy in &layout {
+                    dst_point[copy.dst_offset..copy.dst_offset + copy.len]
+                        .copy_from_slice(&src_point[copy.src_offset..copy.src_offset + copy.len]);
+                }
+            }
+
+            if dst_vlr.uses_variable_size_chunks() {
+                // The points keep their chunks
+                let mut chunks = Vec::with_capacity(batch.len());
+                let mut rest = dst_points.as_slice();
+                for entry in batch {
+                    let (chunk, r) = rest.split_at(entry.point_count as usize * dst_point_size);
+                    chunks.push(chunk);
+                    rest = r;
+                }
+                pool.install(|| compressor.compress_chunks(chunks))?;
+            } else {
+                pool.install(|| compressor.compress_many(&dst_points))?;
+            }
+            progress.advance(src_points.len())?;
+        }
+        pool.install(|| compressor.done()).map_err(into_py_err)?;
+        compressor.get_mut().flush()?;
+        Ok(())
+    })?;
+    Ok(LazVlr { vlr: dst_vlr })
+}
@@ -0,0 +1,316 @@
+//! Integrity verification of LAZ files.
+//!
+//! The whole point data is checked: the offset to the chunk table,
+//! the chunk table itself, the number of points, and the decompression
+//! of each chunk. The problems found are gathered in a report.
+use std::io::{Read, Seek, SeekFrom};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use laz::laszip::ChunkTableEntry;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use crate::adapters::Reader;
+use crate::las::LasMetadata;
+use crate::reader::read_metadata;
+use crate::threads::{Pool, ThreadPool};
+use crate::{into_py_err, ChunkTable, LazVlr};
+
+/// A chunk that could not be decompressed, or whose table entry is wrong
+#[pyclass(frozen, skip_from_py_object)]
+#[derive(Clone)]
+pub(crate) struct BadChunk {
+    /// Index of the chunk in the chunk table
+    #[pyo3(get)]
+    index: usize,
+    /// Start and end of the chunk's bytes in the file
+    #[pyo3(get)]
+    byte_range: (u64, u64),
+    #[pyo3(get)]
+    reason: String,
+}
+
+#[pymethods]
+impl BadChunk {
+    fn __repr__(&self) -> String {
+        format!(
+            "<BadChunk(index: {}, bytes: {}..{}, reason: {})>",
+            self.index, self.byte_range.0, self.byte_range.1, self.reason
+        )
+    }
+}
+
+/// The result of `validate`
+#[pyclass(frozen)]
+pub(crate) struct ValidationReport {
+    /// Number of points, according to the header
+    #[pyo3(get)]
+    point_count: u64,
+    #[pyo3(get)]
+    offset_to_chunk_table: Option<i64>,
+    /// `None` if it could not be read
+    #[pyo3(get)]
+    chunk_table: Option<ChunkTable>,
+    /// The problems which are not specific to a chunk
+    #[pyo3(get)]
+    errors: Vec<String>,
+    #[pyo3(get)]
+    bad_chunks: Vec<BadChunk>,
+}
+
+#[pymethods]
+impl ValidationReport {
+    /// Whether no problem was found
+    #[getter]
+    fn is_valid(&self) -> bool {
+        self.errors.is_empty() && self.bad_chunks.is_empty()
+    }
+
+    fn __bool__(&self) -> bool {
+        self.is_valid()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<ValidationReport(valid: {}, errors: {}, bad chunks: {})>",
+            self.is_valid(),
+            self.errors.len(),
+            self.bad_chunks.len()
+        )
+    }
+}
+
+impl ValidationReport {
+    fn error(mut self, message: String) -> Self {
+        self.errors.push(message);
+        self
+    }
+}
+
+/// Decompresses the chunk, returns why it failed
+fn check_chunk(compressed: &[u8], vlr: &laz::LazVlr, entry: ChunkTableEntry) -> Option<String> {
+    let Some(num_bytes) = entry
+        .point_count
+        .checked_mul(vlr.items_size())
+        .and_then(|num_bytes| usize::try_from(num_bytes).ok())
+    else {
+        return Some(format!(
+            "the {} points of the chunk do not fit in memory",
+            entry.point_count
+        ));
+    };
+    let mut points = vec![0u8; num_bytes];
+    laz::par_decompress_selective(
+        compressed,
+        &mut points,
+        vlr,
+        &[entry],
+        laz::DecompressionSelection::all(),
+    )
+    .err()
+    .map(|error| error.to_string())
+}
+
+/// Real number of points of each chunk, with fixed size chunks
+/// the last chunk's count comes from the header's point count.
+fn chunk_point_counts(
+    entries: &[ChunkTableEntry],
+    vlr: &laz::LazVlr,
+    point_count: u64,
+) -> Vec<u64> {
+    let mut counts = entries
+        .iter()
+        .map(|entry| entry.point_count)
+        .collect::<Vec<u64>>();
+    if !vlr.uses_variable_size_chunks() {
+        if let Some(last) = counts.len().checked_sub(1) {
+            let before_last = last as u64 * u64::from(vlr.chunk_size());
+            if point_count > before_last {
+                counts[last] = counts[last].min(point_count - before_last);
+            }
+        }
+    }
+    counts
+}
+
+fn validate_points(
+    py: Python,
+    source: &mut Reader,
+    metadata: &LasMetadata,
+    vlr: &laz::LazVlr,
+    pool: &Pool,
+) -> PyResult<ValidationReport> {
+    let header = &metadata.header;
+    let mut report = ValidationReport {
+        point_count: header.point_count,
+        offset_to_chunk_table: None,
+        chunk_table: None,
+        errors: Vec::new(),
+        bad_chunks: Vec::new(),
+    };
+
+    let data_start = u64::from(header.offset_to_point_data);
+    let first_chunk = data_start + std::mem::size_of::<i64>() as u64;
+    let file_len = source.seek(SeekFrom::End(0))?;
+    let end_of_points = if header.number_of_evlrs > 0 && header.start_of_first_evlr > data_start {
+        header.start_of_first_evlr
+    } else {
+        file_len
+    };
+
+    source.seek(SeekFrom::Start(data_start))?;
+    let Ok(offset) = source.read_i64::<LittleEndian>() else {
+        return Ok(report.error("The file ends before the offset to the chunk table".to_string()));
+    };
+    report.offset_to_chunk_table = Some(offset);
+    if offset == -1 {
+        return Ok(report.error("The chunk table was not written (its offset is -1)".to_string()));
+    }
+    if offset < first_chunk as i64 || offset as u64 >= end_of_points {
+        return Ok(report.error(format!(
+            "The offset to the chunk table ({}) is not in the point data ({}..{})",
+            offset, first_chunk, end_of_points
+        )));
+    }
+
+    source.seek(SeekFrom::Start(data_start))?;
+    let chunk_table = match laz::laszip::ChunkTable::read_from(&mut *source, vlr) {
+        Ok(chunk_table) => chunk_table,
+        Err(error) => {
+            return Ok(report.error(format!("The chunk table could not be read: {}", error)))
+        }
+    };
+    let entries = chunk_table.as_ref().to_vec();
+    report.chunk_table = Some(ChunkTable::from(chunk_table));
+
+    let num_bytes = entries
+        .iter()
+        .fold(0u64, |total, entry| total.saturating_add(entry.byte_count));
+    if first_chunk.saturating_add(num_bytes) != offset as u64 {
+        report.errors.push(format!(
+            "The chunks take {} bytes, but the chunk table is {} bytes after the first chunk",
+            num_bytes,
+            offset as u64 - first_chunk
+        ));
+    }
+
+    if vlr.uses_variable_size_chunks() {
+        let total = entries
+            .iter()
+            .fold(0u64, |total, entry| total.saturating_add(entry.point_count));
+        if total != header.point_count {
+            report.errors.push(format!(
+                "The chunks hold {} points, the header says there are {}",
+                total, header.point_count
+            ));
+        }
+    } else {
+        let expected = header.point_count.div_ceil(u64::from(vlr.chunk_size()));
+        if expected != entries.len() as u64 {
+            report.errors.push(format!(
+                "The chunk table has {} chunks, the {} points of the header need {}",
+                entries.len(),
+                header.point_count,
+                expected
+            ));
+        }
+    }
+
+    // The chunks are checked in batches, so that only a few of them are in memory
+    let counts = chunk_point_counts(&entries, vlr, header.point_count);
+    let (max_points, max_points_name) = if vlr.uses_variable_size_chunks() {
+        (header.point_count, "the point count of the header")
+    } else {
+        (u64::from(vlr.chunk_size()), "the chunk size")
+    };
+    let batch_len = pool.install(rayon::current_num_threads) * 2;
+    let mut position = first_chunk;
+    let mut index = 0;
+    while index < entries.len() {
+        let mut batch = Vec::with_capacity(batch_len);
+        while index < entries.len() && batch.len() < batch_len {
+            let entry = entries[index];
+            let byte_range = (position, position.saturating_add(entry.byte_count));
+            position = byte_range.1;
+            let bad_chunk = |reason: String| BadChunk {
+                index,
+                byte_range,
+                reason,
+            };
+            if byte_range.1 > offset as u64 {
+                report
+                    .bad_chunks
+                    .push(bad_chunk("the chunk overlaps the chunk table".to_string()));
+            } else if counts[index] > max_points {
+                // The points are not decompressed, they may not fit in memory
+                report.bad_chunks.push(bad_chunk(format!(
+                    "the chunk has {} points, more than {} ({})",
+                    counts[index], max_points_name, max_points
+                )));
+            } else if entry.byte_count == 0 && counts[index] != 0 {
+                report
+                    .bad_chunks
+                    .push(bad_chunk("the chunk has points but no bytes".to_string()));
+            } else {
+                let mut compressed = vec![0u8; entry.byte_count as usize];
+                source.seek(SeekFrom::Start(byte_range.0))?;
+                source.read_exact(&mut compressed)?;
+                let entry = ChunkTableEntry {
+                    point_count: counts[index],
+                    byte_count: entry.byte_count,
+                };
+                batch.push((index, byte_range, compressed, entry));
+            }
+            index += 1;
+        }
+
+        let bad_chunks = py.detach(|| {
+            pool.install(|| {
+                batch
+                    .par_iter()
+                    .filter_map(|(index, byte_range, compressed, entry)| {
+                        check_chunk(compressed, vlr, *entry).map(|reason| BadChunk {
+                            index: *index,
+                            byte_range: *byte_range,
+                            reason,
+                        })
+                    })
+                    .collect::<Vec<BadChunk>>()
+            })
+        });
+        report.bad_chunks.extend(bad_chunks);
+    }
+    report.bad_chunks.sort_by_key(|bad_chunk| bad_chunk.index);
+    Ok(report)
+}
+
+/// Checks the integrity of the point data of a LAZ file.
+///
+/// `source` is a path or a file object, read from the start of the LAS file.
+/// The LasZip VLR of the file is used, unless `vlr` is given.
+///
+/// Checks the offset to the chunk table, the chunk table entries,
+/// the number of points against the header, and decompresses every chunk.
+/// The compressed data has no checksum, so a chunk whose bytes were altered
+/// may still decompress (to wrong values), but truncated chunks are detected.
+#[pyfunction]
+#[pyo3(signature = (source, vlr = None, num_threads = None, thread_pool = None))]
+pub(crate) fn validate<'py>(
+    py: Python<'py>,
+    source: Py<PyAny>,
+    vlr: Option<PyRef<'py, LazVlr>>,
+    num_threads: Option<usize>,
+    thread_pool: Option<PyRef<'py, ThreadPool>>,
+) -> PyResult<ValidationReport> {
+    let pool = Pool::from_args(num_threads, thread_pool.as_deref())?;
+    let mut source = Reader::new(py, source)?;
+    source.seek(SeekFrom::Start(0))?;
+    let (metadata, laz_vlr) = match vlr {
+        Some(vlr) => (
+            LasMetadata::read_from(&mut source).map_err(into_py_err)?,
+            vlr.vlr.clone(),
+        ),
+        None => read_metadata(&mut source)?,
+    };
+    validate_points(py, &mut source, &metadata, &laz_vlr, &pool)
+}
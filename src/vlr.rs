@@ -0,0 +1,183 @@
+//! Details of the LasZip VLR which laz does not give access to,
+//! they are read from the VLR's record data.
+use std::io::Read;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use pyo3::prelude::*;
+
+use crate::{into_py_err, LazrsError};
+
+/// Size of the record data that comes before the items
+const HEADER_SIZE: usize = 32;
+
+/// The fields of the LasZip VLR record data that come before the items
+/// (except the chunk size)
+pub(crate) struct VlrHeader {
+    pub(crate) compressor: u16,
+    pub(crate) coder: u16,
+    pub(crate) version_major: u8,
+    pub(crate) version_minor: u8,
+    pub(crate) version_revision: u16,
+    pub(crate) options: u32,
+    pub(crate) number_of_special_evlrs: i64,
+    pub(crate) offset_to_special_evlrs: i64,
+}
+
+impl VlrHeader {
+    fn read_from<R: Read>(src: &mut R) -> std::io::Result<Self> {
+        let compressor = src.read_u16::<LittleEndian>()?;
+        let coder = src.read_u16::<LittleEndian>()?;
+        let version_major = src.read_u8()?;
+        let version_minor = src.read_u8()?;
+        let version_revision = src.read_u16::<LittleEndian>()?;
+        let options = src.read_u32::<LittleEndian>()?;
+        // The chunk size, laz gives access to it
+        let _chunk_size = src.read_u32::<LittleEndian>()?;
+        Ok(Self {
+            compressor,
+            coder,
+            version_major,
+            version_minor,
+            version_revision,
+            options,
+            number_of_special_evlrs: src.read_i64::<LittleEndian>()?,
+            offset_to_special_evlrs: src.read_i64::<LittleEndian>()?,
+        })
+    }
+
+    pub(crate) fn of(vlr: &laz::LazVlr) -> std::io::Result<Self> {
+        let mut record_data = Vec::new();
+        vlr.write_to(&mut record_data)?;
+        Self::read_from(&mut record_data.as_slice())
+    }
+}
+
+/// Name of the compressor type, as in LASzip
+pub(crate) fn compressor_name(compressor: u16) -> &'static str {
+    match compressor {
+        0 => "None",
+        1 => "PointWise",
+        2 => "PointWiseChunked",
+        3 => "LayeredChunked",
+        _ => "Unknown",
+    }
+}
+
+/// The `(name, type id, size)` of the item types,
+/// the size of the extra bytes items (`Byte` and `Byte14`) is the number of extra bytes
+const ITEM_TYPES: [(&str, u16, Option<u16>); 10] = [
+    ("Byte", 0, None),
+    ("Point10", 6, Some(20)),
+    ("GpsTime", 7, Some(8)),
+    ("RGB12", 8, Some(6)),
+    ("WavePacket13", 9, Some(29)),
+    ("Point14", 10, Some(30)),
+    ("RGB14", 11, Some(6)),
+    ("RGBNIR14", 12, Some(8)),
+    ("WavePacket14", 13, Some(29)),
+    ("Byte14", 14, None),
+];
+
+pub(crate) fn item_type_name(type_id: u16) -> &'static str {
+    ITEM_TYPES
+        .iter()
+        .find(|(_, id, _)| *id == type_id)
+        .map_or("Unknown", |(name, _, _)| name)
+}
+
+/// Returns the laz items, a `laz::LazItem` can only be created by reading it,
+/// so they are read from the record data of a VLR
+pub(crate) fn laz_items(items: &[PyRef<'_, LazItem>]) -> PyResult<Vec<laz::LazItem>> {
+    let num_items = u16::try_from(items.len())
+        .map_err(|_| PyErr::new::<LazrsError, _>("There are too many items"))?;
+    let mut record_data = Vec::new();
+    laz::LazVlrBuilder::default()
+        .with_point_format(0, 0)
+        .map_err(into_py_err)?
+        .build()
+        .write_to(&mut record_data)?;
+    record_data.truncate(HEADER_SIZE);
+    record_data.write_u16::<LittleEndian>(num_items)?;
+    for item in items {
+        record_data.write_u16::<LittleEndian>(item.type_id)?;
+        record_data.write_u16::<LittleEndian>(item.size)?;
+        record_data.write_u16::<LittleEndian>(item.version)?;
+    }
+    let vlr = laz::LazVlr::read_from(record_data.as_slice()).map_err(into_py_err)?;
+    Ok(vlr.items().clone())
+}
+
+/// An item of the LasZip VLR, a part of the point record and the version
+/// of the compression used for it
+#[pyclass(frozen, skip_from_py_object)]
+#[derive(Clone)]
+pub(crate) struct LazItem {
+    /// Name of the type (`"Point10"`, `"GpsTime"`, `"RGB12"`, `"Byte"`, `"Point14"`...)
+    #[pyo3(get)]
+    item_type: &'static str,
+    /// The type as stored in the VLR
+    #[pyo3(get)]
+    type_id: u16,
+    /// Size in bytes of the item in the point record
+    #[pyo3(get)]
+    size: u16,
+    #[pyo3(get)]
+    pub(crate) version: u16,
+}
+
+impl From<&laz::LazItem> for LazItem {
+    fn from(item: &laz::LazItem) -> Self {
+        Self {
+            item_type: item_type_name(item.item_type().into()),
+            type_id: item.item_type().into(),
+            size: item.size(),
+            version: item.version(),
+        }
+    }
+}
+
+#[pymethods]
+impl LazItem {
+    /// `size` is only needed for the extra bytes (`"Byte"` and `"Byte14"`),
+    /// it is their number.
+    #[new]
+    #[pyo3(signature = (item_type, version, size = None))]
+    fn new(item_type: &str, version: u16, size: Option<u16>) -> PyResult<Self> {
+        let (name, type_id, item_size) = ITEM_TYPES
+            .iter()
+            .find(|(name, _, _)| *name == item_type)
+            .ok_or_else(|| {
+                PyErr::new::<LazrsError, _>(format!("Unknown item type '{}'", item_type))
+            })?;
+        let size = match (item_size, size) {
+            (Some(item_size), None) => *item_size,
+            (Some(item_size), Some(size)) if size == *item_size => size,
+            (None, Some(size)) => size,
+            (Some(item_size), Some(size)) => {
+                return Err(PyErr::new::<LazrsError, _>(format!(
+                    "The size of a {} item is {}, not {}",
+                    name, item_size, size
+                )))
+            }
+            (None, None) => {
+                return Err(PyErr::new::<LazrsError, _>(format!(
+                    "The size of a {} item must be given",
+                    name
+                )))
+            }
+        };
+        Ok(Self {
+            item_type: name,
+            type_id: *type_id,
+            size,
+            version,
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<LazItem(type: {}, size: {}, version: {})>",
+            self.item_type, self.size, self.version
+        )
+    }
+}
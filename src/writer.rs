@@ -0,0 +1,301 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use pyo3::prelude::*;
+
+use crate::adapters::Writer;
+use crate::las::{base_point_size, LasHeader, Vlr};
+use crate::threads::Pool;
+use crate::{as_bytes, buffer_length_error, into_py_err, LazVlr, LazrsError};
+
+/// Statistics the LAS header needs, gathered on the points as they are written
+struct PointStats {
+    point_count: u64,
+    mins: [i32; 3],
+    maxs: [i32; 3],
+    number_of_points_by_return: [u64; 15],
+}
+
+impl Default for PointStats {
+    fn default() -> Self {
+        Self {
+            point_count: 0,
+            mins: [i32::MAX; 3],
+            maxs: [i32::MIN; 3],
+            number_of_points_by_return: [0u64; 15],
+        }
+    }
+}
+
+impl PointStats {
+    fn update(&mut self, points: &[u8], point_size: usize, point_format_id: u8) {
+        // Return number is in the first 3 bits for point formats < 6,
+        // in the first 4 bits otherwise
+        let return_number_mask = if point_format_id >= 6 { 0x0F } else { 0x07 };
+
+        for point in points.chunks_exact(point_size) {
+            for i in 0..3 {
+                let value = i32::from_le_bytes(point[i * 4..(i + 1) * 4].try_into().unwrap());
+                self.mins[i] = std::cmp::min(self.mins[i], value);
+                self.maxs[i] = std::cmp::max(self.maxs[i], value);
+            }
+            let return_number = (point[14] & return_number_mask) as usize;
+            if (1..=self.number_of_points_by_return.len()).contains(&return_number) {
+                self.number_of_points_by_return[return_number - 1] += 1;
+            }
+        }
+        self.point_count += (points.len() / point_size) as u64;
+    }
+
+    /// Updates the header counts and bounds
+    fn apply_to(&self, header: &mut LasHeader) {
+        header.point_count = self.point_count;
+        header.number_of_points_by_return = self.number_of_points_by_return;
+        if self.point_count == 0 {
+            header.mins = (0.0, 0.0, 0.0);
+            header.maxs = (0.0, 0.0, 0.0);
+            return;
+        }
+        let scales = [header.scales.0, header.scales.1, header.scales.2];
+        let offsets = [header.offsets.0, header.offsets.1, header.offsets.2];
+        let scale = |i: usize, value: i32| f64::from(value) * scales[i] + offsets[i];
+        header.mins = (
+            scale(0, self.mins[0]),
+            scale(1, self.mins[1]),
+            scale(2, self.mins[2]),
+        );
+        header.maxs = (
+            scale(0, self.maxs[0]),
+            scale(1, self.maxs[1]),
+            scale(2, self.maxs[2]),
+        );
+    }
+}
+
+/// The destination, with positions relative to the start of the LAS file,
+/// so that the offset to the chunk table laz writes is relative to it too
+struct LasDest {
+    dest: Writer,
+    /// Position of the start of the LAS file in the destination
+    start: u64,
+}
+
+impl Write for LasDest {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.dest.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.dest.flush()
+    }
+}
+
+impl Seek for LasDest {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(position) => SeekFrom::Start(position + self.start),
+            pos => pos,
+        };
+        self.dest.seek(pos)?.checked_sub(self.start).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to before the start of the LAS file",
+            )
+        })
+    }
+}
+
+type DynCompressor = Box<dyn laz::LazCompressorWithInner<LasDest> + Send + Sync>;
+
+/// Writer of LAZ files.
+///
+/// Writes the header, the VLRs (including the LasZip VLR) and
+/// compresses points, on close the chunk table and EVLRs are written
+/// and the header is updated with the point count, bounds and
+/// number of points by return of the points written.
+///
+/// The LAS file is considered to start where the `dest` position is
+/// when the writer is created.
+///
+/// With `parallel`, the points are compressed by several threads,
+/// which requires fixed-size chunks.
+#[pyclass]
+pub(crate) struct LazWriter {
+    header: LasHeader,
+    evlrs: Vec<Vlr>,
+    stats: PointStats,
+    compressor: Option<DynCompressor>,
+    pool: Pool,
+}
+
+impl LazWriter {
+    fn compressor(&mut self) -> PyResult<&mut DynCompressor> {
+        self.compressor
+            .as_mut()
+            .ok_or_else(|| PyErr::new::<LazrsError, _>("The writer is closed"))
+    }
+}
+
+#[pymethods]
+impl LazWriter {
+    #[new]
+    #[pyo3(signature = (dest, header, vlrs = Vec::new(), evlrs = Vec::new(), laz_vlr = None, parallel = false))]
+    fn new(
+        py: Python,
+        dest: Py<PyAny>,
+        mut header: LasHeader,
+        vlrs: Vec<Vlr>,
+        evlrs: Vec<Vlr>,
+        laz_vlr: Option<PyRef<LazVlr>>,
+        parallel: bool,
+    ) -> PyResult<Self> {
+        if header.point_format_id >= 6 && header.version_minor < 4 {
+            return Err(PyErr::new::<LazrsError, _>(format!(
+                "Point format {} requires LAS 1.4",
+                header.point_format_id
+            )));
+        }
+        if !evlrs.is_empty() && header.version_minor < 4 {
+            return Err(PyErr::new::<LazrsError, _>("EVLRs requires LAS 1.4"));
+        }
+
+        let laz_vlr = match laz_vlr {
+            Some(vlr) => vlr.vlr.clone(),
+            None => {
+                let num_extra_bytes = base_point_size(header.point_format_id)
+                    .and_then(|size| header.point_size.checked_sub(size))
+                    .ok_or_else(|| {
+                        PyErr::new::<LazrsError, _>(format!(
+                            "Point size {} is invalid for point format {}",
+                            header.point_size, header.point_format_id
+                        ))
+                    })?;
+                laz::LazVlrBuilder::default()
+                    .with_point_format(header.point_format_id, num_extra_bytes)
+                    .map_err(into_py_err)?
+                    .build()
+            }
+        };
+        if parallel && laz_vlr.uses_variable_size_chunks() {
+            return Err(PyErr::new::<LazrsError, _>(
+                "Variable-size chunks cannot be compressed in parallel",
+            ));
+        }
+        if laz_vlr.items_size() != u64::from(header.point_size) {
+            return Err(PyErr::new::<LazrsError, _>(format!(
+                "Point size {} does not match the LasZip VLR's item size {}",
+                header.point_size,
+                laz_vlr.items_size()
+            )));
+        }
+
+        let mut laszip_record_data = Vec::new();
+        laz_vlr.write_to(&mut laszip_record_data)?;
+        let vlrs = vlrs
+            .into_iter()
+            .filter(|vlr| !vlr.is_laszip_vlr())
+            .chain(std::iter::once(Vlr {
+                user_id: laz::LazVlr::USER_ID.to_string(),
+                record_id: laz::LazVlr::RECORD_ID,
+                description: laz::LazVlr::DESCRIPTION.to_string(),
+                data: laszip_record_data,
+            }))
+            .collect::<Vec<_>>();
+
+        header.header_size = header.standard_size();
+        header.number_of_vlrs = vlrs.len() as u32;
+        let offset_to_point_data = u64::from(header.header_size)
+            + vlrs.iter().map(|vlr| vlr.size_in_file(false)).sum::<u64>();
+        header.offset_to_point_data = u32::try_from(offset_to_point_data)
+            .map_err(|_| PyErr::new::<LazrsError, _>("VLRs are too big"))?;
+        header.number_of_evlrs = 0;
+        header.start_of_first_evlr = 0;
+
+        let mut dest = Writer::create(py, dest)?;
+        let start = dest.stream_position()?;
+        let mut dest = LasDest { dest, start };
+        header.write_to(&mut dest)?;
+        for vlr in &vlrs {
+            vlr.write_to(&mut dest, false)?;
+        }
+
+        let mut compressor: DynCompressor = if parallel {
+            Box::new(laz::ParLasZipCompressor::new(dest, laz_vlr).map_err(into_py_err)?)
+        } else {
+            Box::new(laz::LasZipCompressor::new(dest, laz_vlr).map_err(into_py_err)?)
+        };
+        compressor
+            .reserve_offset_to_chunk_table()
+            .map_err(into_py_err)?;
+
+        Ok(Self {
+            header,
+            evlrs,
+            stats: PointStats::default(),
+            compressor: Some(compressor),
+            pool: Pool::default(),
+        })
+    }
+
+    /// The header, its counts and bounds are only up to date
+    /// once the writer is closed.
+    #[getter]
+    fn header(&self) -> LasHeader {
+        self.header.clone()
+    }
+
+    #[getter]
+    fn closed(&self) -> bool {
+        self.compressor.is_none()
+    }
+
+    fn compress_many<'py>(&mut self, py: Python<'py>, points: &Bound<'py, PyAny>) -> PyResult<()> {
+        let point_bytes = as_bytes(points)?;
+        let point_size = usize::from(self.header.point_size);
+        if point_bytes.len() % point_size != 0 {
+            return Err(buffer_length_error(
+                format!(
+                    "The number of bytes ({}) is not a multiple of the point size ({})",
+                    point_bytes.len(),
+                    point_size
+                ),
+                point_bytes.len() - point_bytes.len() % point_size,
+                point_bytes.len(),
+            ));
+        }
+
+        let point_format_id = self.header.point_format_id;
+        let pool = self.pool.clone();
+        let compressor = self.compressor()?;
+        py.detach(|| pool.install(|| compressor.compress_many(&point_bytes)))
+            .map_err(into_py_err)?;
+        self.stats.update(&point_bytes, point_size, point_format_id);
+        Ok(())
+    }
+
+    /// Finishes the compression, writes the EVLRs and updates the header.
+    ///
+    /// Calling it on a closed writer does nothing.
+    fn close(&mut self, py: Python) -> PyResult<()> {
+        let Some(mut compressor) = self.compressor.take() else {
+            return Ok(());
+        };
+        py.detach(|| self.pool.install(|| compressor.done()))
+            .map_err(into_py_err)?;
+
+        let dest = compressor.inner_mut();
+        if !self.evlrs.is_empty() {
+            self.header.start_of_first_evlr = dest.stream_position()?;
+            self.header.number_of_evlrs = self.evlrs.len() as u32;
+            for evlr in &self.evlrs {
+                evlr.write_to(dest, true)?;
+            }
+        }
+
+        self.stats.apply_to(&mut self.header);
+        dest.seek(SeekFrom::Start(0))?;
+        self.header.write_to(dest)?;
+        dest.seek(SeekFrom::End(0))?;
+        dest.flush()?;
+        Ok(())
+    }
+}
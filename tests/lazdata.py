@@ -0,0 +1,41 @@
+"""Points and compressed data shared by the tests.
+
+The points have sequential X and Y and constant other fields, like real files
+they compress well, which is when the compressed data tells the least about
+where the points of a chunk end.
+"""
+import io
+import struct
+
+import lazrs
+
+CHUNK_SIZE = 1000
+
+
+def sequential_points(point_format_id, count, chunk_size=CHUNK_SIZE, variable_size=False):
+    """Returns the LazVlr and the bytes of `count` points"""
+    vlr = lazrs.LazVlr.new_for_compression(point_format_id, 0, variable_size, chunk_size)
+    point_size = vlr.item_size()
+    # Return 1 of 1
+    returns = 0x11 if point_format_id >= 6 else 0x09
+    points = bytearray(point_size * count)
+    for i in range(count):
+        struct.pack_into("<3iHB", points, i * point_size, i, 2 * i, 0, 100, returns)
+    return vlr, bytes(points)
+
+
+def compress(vlr, points):
+    """Returns the point data: the offset to the chunk table, the chunks and the table"""
+    dest = io.BytesIO()
+    compressor = lazrs.LasZipCompressor(dest, vlr)
+    compressor.compress_many(points)
+    compressor.done()
+    return dest.getvalue()
+
+
+def write_laz(path, point_format_id, vlr, points):
+    """Writes a LAZ file with the points"""
+    with open(path, "wb") as dest:
+        writer = lazrs.LazWriter(dest, lazrs.LasHeader(point_format_id), laz_vlr=vlr)
+        writer.compress_many(points)
+        writer.close()
@@ -0,0 +1,33 @@
+import io
+
+import pytest
+
+import lazrs
+from lazdata import compress, sequential_points
+
+COUNT = 2_500
+APPENDED = 700
+
+
+@pytest.mark.parametrize("appender_class", [lazrs.LasZipAppender, lazrs.ParLasZipAppender])
+@pytest.mark.parametrize("point_format_id", [1, 6])
+def test_append(appender_class, point_format_id):
+    vlr, points = sequential_points(point_format_id, COUNT + APPENDED)
+    point_size = vlr.item_size()
+    dest = io.BytesIO(compress(vlr, points[: COUNT * point_size]))
+
+    appender = appender_class(dest, vlr.record_data(), COUNT)
+    appender.compress_many(points[COUNT * point_size :])
+    appender.done()
+
+    decompressed = bytearray(len(points))
+    lazrs.decompress_points(dest.getvalue(), vlr.record_data(), decompressed, False)
+    assert decompressed == points
+
+
+@pytest.mark.parametrize("point_count", [2_000, 3_001])
+def test_append_wrong_point_count(point_count):
+    vlr, points = sequential_points(1, COUNT)
+    dest = io.BytesIO(compress(vlr, points))
+    with pytest.raises(lazrs.LazrsError):
+        lazrs.LasZipAppender(dest, vlr.record_data(), point_count)
@@ -0,0 +1,21 @@
+import io
+
+import pytest
+
+import lazrs
+from lazdata import compress, sequential_points
+
+COUNT = 2_500
+
+
+def test_fixed_size_chunk_table_with_point_count():
+    vlr, points = sequential_points(1, COUNT)
+    chunk_table = lazrs.read_chunk_table(io.BytesIO(compress(vlr, points)), vlr)
+
+    # The last entry says the chunk is full
+    assert chunk_table.total_points == 3_000
+    assert chunk_table.chunk_index_for_point(2_600) == 2
+
+    assert chunk_table.chunk_index_for_point(2_499, point_count=COUNT) == 2
+    with pytest.raises(IndexError):
+        chunk_table.chunk_index_for_point(2_500, point_count=COUNT)
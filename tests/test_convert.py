@@ -0,0 +1,15 @@
+import io
+
+import pytest
+
+import lazrs
+from lazdata import sequential_points
+
+
+def test_compress_many_rejects_variable_size_chunks():
+    _, points = sequential_points(1, 10)
+    vlr, _ = sequential_points(6, 0, chunk_size=None, variable_size=True)
+    converter = lazrs.PointConverter(1, 6)
+    compressor = lazrs.ParLasZipCompressor(io.BytesIO(), vlr)
+    with pytest.raises(lazrs.LazrsError, match="variable-size chunks"):
+        converter.compress_many(compressor, points)
@@ -0,0 +1,55 @@
+import io
+
+import pytest
+
+import lazrs
+from lazdata import CHUNK_SIZE, compress, sequential_points
+
+COUNT = 2_500
+
+
+@pytest.mark.parametrize("indices", [[2], [0, 2], [2, 1]])
+def test_decompress_chunks_point_wise(indices):
+    vlr, points = sequential_points(1, COUNT)
+    data = compress(vlr, points)
+    chunk_table = lazrs.read_chunk_table(io.BytesIO(data), vlr)
+    point_size = vlr.item_size()
+
+    # The output is larger than the points, it does not tell where the last chunk ends
+    out = bytearray(len(points))
+    offsets = lazrs.decompress_chunks(
+        io.BytesIO(data), vlr, chunk_table, indices, out, point_count=COUNT
+    )
+    expected = b"".join(
+        points[index * CHUNK_SIZE * point_size : (index + 1) * CHUNK_SIZE * point_size]
+        for index in indices
+    )
+    assert out[: len(expected)] == expected
+    # Only the 500 points of the last chunk are written
+    assert not any(out[len(expected) :])
+    counts = [min(CHUNK_SIZE, COUNT - index * CHUNK_SIZE) for index in indices]
+    assert offsets == [sum(counts[:i]) for i in range(len(counts))]
+
+
+def test_decompress_chunks_point_wise_requires_point_count():
+    vlr, points = sequential_points(1, COUNT)
+    data = compress(vlr, points)
+    chunk_table = lazrs.read_chunk_table(io.BytesIO(data), vlr)
+    out = bytearray(len(points))
+    with pytest.raises(lazrs.LazrsError):
+        lazrs.decompress_chunks(io.BytesIO(data), vlr, chunk_table, [2], out)
+    # The full chunks do not need it
+    lazrs.decompress_chunks(io.BytesIO(data), vlr, chunk_table, [1], out)
+    point_size = vlr.item_size()
+    expected = points[CHUNK_SIZE * point_size : 2 * CHUNK_SIZE * point_size]
+    assert out[: len(expected)] == expected
+
+
+def test_decompress_chunks_layered():
+    vlr, points = sequential_points(6, COUNT)
+    data = compress(vlr, points)
+    chunk_table = lazrs.read_chunk_table(io.BytesIO(data), vlr)
+    point_size = vlr.item_size()
+    out = bytearray(len(points))
+    lazrs.decompress_chunks(data[8:], vlr, chunk_table, [2], out)
+    assert out[: 500 * point_size] == points[2 * CHUNK_SIZE * point_size :]
@@ -0,0 +1,69 @@
+import io
+import struct
+
+import pytest
+
+import lazrs
+from lazdata import compress, sequential_points
+
+COUNT = 2_500
+
+
+def chunks_and_table(vlr, data):
+    offset = struct.unpack_from("<q", data)[0]
+    chunk_table = lazrs.read_chunk_table(io.BytesIO(data), vlr)
+    return data[8:offset], chunk_table
+
+
+@pytest.mark.parametrize("start, count", [(2_400, 100), (950, 1_100), (0, COUNT)])
+def test_decompress_range_point_wise(start, count):
+    vlr, points = sequential_points(1, COUNT)
+    data = compress(vlr, points)
+    chunks, chunk_table = chunks_and_table(vlr, data)
+    point_size = vlr.item_size()
+    expected = points[start * point_size : (start + count) * point_size]
+
+    out = bytearray(count * point_size)
+    lazrs.decompress_range(
+        chunks, vlr.record_data(), chunk_table, start, count, out, point_count=COUNT
+    )
+    assert out == expected
+
+    for decompressor in (
+        lazrs.LasZipDecompressor(io.BytesIO(data), vlr.record_data()),
+        lazrs.ParLasZipDecompressor(io.BytesIO(data), vlr.record_data()),
+    ):
+        out = bytearray(count * point_size)
+        decompressor.decompress_range(start, count, out, point_count=COUNT)
+        assert out == expected
+
+
+def test_decompress_range_point_wise_requires_point_count():
+    vlr, points = sequential_points(1, COUNT)
+    data = compress(vlr, points)
+    chunks, chunk_table = chunks_and_table(vlr, data)
+    out = bytearray(100 * vlr.item_size())
+    with pytest.raises(lazrs.LazrsError):
+        lazrs.decompress_range(chunks, vlr.record_data(), chunk_table, 2_400, 100, out)
+    with pytest.raises(lazrs.LazrsError):
+        decompressor = lazrs.LasZipDecompressor(io.BytesIO(data), vlr.record_data())
+        decompressor.decompress_range(2_400, 100, out)
+
+
+def test_decompress_range_past_the_points():
+    vlr, points = sequential_points(1, COUNT)
+    chunks, chunk_table = chunks_and_table(vlr, compress(vlr, points))
+    out = bytearray(101 * vlr.item_size())
+    with pytest.raises(lazrs.LazrsError):
+        lazrs.decompress_range(
+            chunks, vlr.record_data(), chunk_table, 2_400, 101, out, point_count=COUNT
+        )
+
+
+def test_decompress_range_layered():
+    vlr, points = sequential_points(6, COUNT)
+    chunks, chunk_table = chunks_and_table(vlr, compress(vlr, points))
+    point_size = vlr.item_size()
+    out = bytearray(100 * point_size)
+    lazrs.decompress_range(chunks, vlr.record_data(), chunk_table, 2_400, 100, out)
+    assert out == points[2_400 * point_size :]
@@ -0,0 +1,37 @@
+import io
+
+import pytest
+
+import lazrs
+from lazdata import CHUNK_SIZE, compress, sequential_points
+
+COUNT = 2_500
+
+
+def decompressors(vlr, data):
+    yield lazrs.LasZipDecompressor(io.BytesIO(data), vlr.record_data())
+    yield lazrs.ParLasZipDecompressor(io.BytesIO(data), vlr.record_data())
+
+
+def test_iter_chunks_with_point_count():
+    vlr, points = sequential_points(1, COUNT)
+    data = compress(vlr, points)
+    for decompressor in decompressors(vlr, data):
+        chunks = list(decompressor.iter_chunks(point_count=COUNT))
+        assert [len(chunk) // vlr.item_size() for chunk in chunks] == [CHUNK_SIZE, CHUNK_SIZE, 500]
+        assert b"".join(chunks) == points
+
+
+def test_iter_chunks_point_wise_requires_point_count():
+    vlr, points = sequential_points(1, COUNT)
+    data = compress(vlr, points)
+    for decompressor in decompressors(vlr, data):
+        with pytest.raises(lazrs.LazrsError):
+            decompressor.iter_chunks()
+
+
+def test_iter_chunks_layered_reads_point_count():
+    vlr, points = sequential_points(6, COUNT)
+    data = compress(vlr, points)
+    for decompressor in decompressors(vlr, data):
+        assert b"".join(decompressor.iter_chunks()) == points
@@ -0,0 +1,22 @@
+import pytest
+
+import lazrs
+from lazdata import sequential_points, write_laz
+
+COUNT = 2_500
+
+
+@pytest.mark.parametrize("point_format_id", [1, 3, 6])
+@pytest.mark.parametrize("mmap", [False, True])
+def test_read_written_file(tmp_path, point_format_id, mmap):
+    vlr, points = sequential_points(point_format_id, COUNT)
+    path = tmp_path / "points.laz"
+    write_laz(path, point_format_id, vlr, points)
+
+    reader = lazrs.LazReader.open(path, mmap=mmap)
+    assert reader.point_count == COUNT
+    assert reader.read_points(COUNT + 1) == points
+
+    point_size = vlr.item_size()
+    reader.seek(2_400)
+    assert reader.read_points(200) == points[2_400 * point_size :]
@@ -0,0 +1,67 @@
+import io
+import struct
+import warnings
+
+import pytest
+
+import lazrs
+from lazdata import CHUNK_SIZE, compress, sequential_points
+
+COUNT = 2_500
+
+
+def lost_offset(data):
+    """Returns the point data with the offset to the chunk table lost, followed by EVLR-like bytes"""
+    return struct.pack("<q", -1) + data[8:] + bytes(200)
+
+
+@pytest.mark.parametrize("point_format_id", [1, 6])
+def test_recover_with_point_count(point_format_id):
+    vlr, points = sequential_points(point_format_id, COUNT)
+    data = lost_offset(compress(vlr, points))
+
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        recovered, chunk_table = lazrs.recover_points(io.BytesIO(data), vlr, COUNT)
+    assert recovered == points
+    assert chunk_table.total_points == COUNT
+
+
+def test_recover_point_wise_without_point_count():
+    vlr, points = sequential_points(1, COUNT)
+    data = lost_offset(compress(vlr, points))
+
+    # The end of the last chunk is where the chunk table starts, but the points
+    # may go on a little further, so all the points are recovered and a warning is issued
+    with pytest.warns(UserWarning, match="point_count"):
+        recovered, chunk_table = lazrs.recover_points(io.BytesIO(data), vlr)
+    assert len(recovered) >= len(points)
+    assert recovered[: len(points)] == points
+    assert len(chunk_table) == 3
+
+
+def test_recover_cut_point_wise_chunk():
+    vlr, points = sequential_points(1, COUNT)
+    data = compress(vlr, points)
+    chunk_table = lazrs.read_chunk_table(io.BytesIO(data), vlr)
+    first_chunks = 8 + chunk_table[0][1] + chunk_table[1][1]
+    # Half of the last chunk is lost
+    cut = data[: first_chunks + chunk_table[2][1] // 2]
+
+    with pytest.warns(UserWarning, match="point_count"):
+        recovered, _ = lazrs.recover_points(io.BytesIO(cut), vlr)
+    assert len(recovered) > 2 * CHUNK_SIZE * vlr.item_size()
+    assert recovered == points[: len(recovered)]
+
+
+def test_repair_point_data():
+    vlr, points = sequential_points(1, COUNT)
+    data = lost_offset(compress(vlr, points))
+
+    dest = io.BytesIO()
+    chunk_table = lazrs.repair_point_data(io.BytesIO(data), dest, vlr, COUNT)
+    assert chunk_table.total_points == COUNT
+    decompressor = lazrs.LasZipDecompressor(io.BytesIO(dest.getvalue()), vlr.record_data())
+    repaired = bytearray(len(points))
+    decompressor.decompress_many(repaired)
+    assert repaired == points
@@ -0,0 +1,21 @@
+import io
+import struct
+
+import pytest
+
+import lazrs
+from lazdata import compress, sequential_points
+
+
+@pytest.mark.parametrize("decompressor_class", [lazrs.LasZipDecompressor, lazrs.ParLasZipDecompressor])
+def test_read_raw_bytes_with_selection(decompressor_class):
+    vlr, points = sequential_points(6, 2_500)
+    data = compress(vlr, points)
+    offset = struct.unpack_from("<q", data)[0]
+    selection = lazrs.DecompressionSelection.xy_returns_channel()
+
+    decompressor = decompressor_class(io.BytesIO(data), vlr.record_data(), selection)
+    # The layers that are not selected are read too
+    raw = bytearray(offset - 8)
+    decompressor.read_raw_bytes_into(raw)
+    assert raw == data[8:offset]
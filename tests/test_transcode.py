@@ -0,0 +1,46 @@
+import io
+
+import pytest
+
+import lazrs
+from lazdata import compress, sequential_points
+
+COUNT = 2_500
+
+
+def test_transcode_point_wise():
+    src_vlr, src_points = sequential_points(3, COUNT)
+    src = io.BytesIO(compress(src_vlr, src_points))
+    dst = io.BytesIO()
+    dst_vlr = lazrs.transcode(src, dst, src_vlr, 1, point_count=COUNT)
+
+    points = bytearray(COUNT * dst_vlr.item_size())
+    lazrs.decompress_points(dst.getvalue(), dst_vlr.record_data(), points, False)
+    # Point format 1 is point format 3 without the RGB
+    expected = b"".join(
+        src_points[i : i + dst_vlr.item_size()]
+        for i in range(0, len(src_points), src_vlr.item_size())
+    )
+    assert points == expected
+
+
+def test_transcode_point_wise_requires_point_count():
+    src_vlr, src_points = sequential_points(3, COUNT)
+    src = io.BytesIO(compress(src_vlr, src_points))
+    with pytest.raises(lazrs.LazrsError):
+        lazrs.transcode(src, io.BytesIO(), src_vlr, 1)
+
+
+def test_transcode_layered():
+    src_vlr, src_points = sequential_points(7, COUNT)
+    src = io.BytesIO(compress(src_vlr, src_points))
+    dst = io.BytesIO()
+    dst_vlr = lazrs.transcode(src, dst, src_vlr, 6)
+
+    points = bytearray(COUNT * dst_vlr.item_size())
+    lazrs.decompress_points(dst.getvalue(), dst_vlr.record_data(), points, False)
+    expected = b"".join(
+        src_points[i : i + dst_vlr.item_size()]
+        for i in range(0, len(src_points), src_vlr.item_size())
+    )
+    assert points == expected
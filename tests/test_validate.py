@@ -0,0 +1,32 @@
+import io
+import struct
+
+import pytest
+
+import lazrs
+from lazdata import sequential_points, write_laz
+
+COUNT = 2_500
+
+
+@pytest.mark.parametrize("point_count", [COUNT + 1, 2**32 - 1])
+def test_validate_reports_overstated_chunk(tmp_path, point_count):
+    vlr, points = sequential_points(6, COUNT, chunk_size=None, variable_size=True)
+    path = tmp_path / "points.laz"
+    write_laz(path, 6, vlr, points)
+    data = path.read_bytes()
+
+    # Rewrite the chunk table with a point count the header cannot hold
+    offset_to_point_data = struct.unpack_from("<I", data, 96)[0]
+    offset = struct.unpack_from("<q", data, offset_to_point_data)[0]
+    source = io.BytesIO(data)
+    source.seek(offset_to_point_data)
+    (entry,) = lazrs.read_chunk_table(source, vlr)
+    dest = io.BytesIO()
+    lazrs.write_chunk_table(dest, [(point_count, entry[1])], vlr)
+
+    report = lazrs.validate(io.BytesIO(data[:offset] + dest.getvalue()))
+    assert not report.is_valid
+    (bad_chunk,) = report.bad_chunks
+    assert bad_chunk.index == 0
+    assert "more than the point count of the header" in bad_chunk.reason
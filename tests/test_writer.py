@@ -0,0 +1,13 @@
+import io
+
+import pytest
+
+import lazrs
+from lazdata import sequential_points
+
+
+def test_parallel_writer_rejects_variable_size_chunks():
+    vlr, _ = sequential_points(6, 0, chunk_size=None, variable_size=True)
+    header = lazrs.LasHeader(6)
+    with pytest.raises(lazrs.LazrsError, match="Variable-size chunks"):
+        lazrs.LazWriter(io.BytesIO(), header, laz_vlr=vlr, parallel=True)